@@ -37,7 +37,8 @@ pub struct KPState {
 impl KPState {
     pub fn random_starting_position() -> Self {
         let first_player_card = KPCard::random_sample();
-        let second_player_card = KPCard::random_sample_neq_other(first_player_card);
+        let second_player_card =
+            KPCard::random_sample_neq_other(first_player_card, &mut thread_rng());
 
         KPState {
             first_player_card,
@@ -82,13 +83,15 @@ impl Game for KPState {
     type PlayerTag = KPPlayer;
     type MoveList = Vec<KPMove>;
 
-    fn randomize_determination(&mut self, observer: Self::PlayerTag) {
+    fn randomize_determination<R: Rng + ?Sized>(&mut self, observer: Self::PlayerTag, rng: &mut R) {
         match observer {
             KPPlayer::First => {
-                self.second_player_card = KPCard::random_sample_neq_other(self.first_player_card)
+                self.second_player_card =
+                    KPCard::random_sample_neq_other(self.first_player_card, rng)
             }
             KPPlayer::Second => {
-                self.first_player_card = KPCard::random_sample_neq_other(self.second_player_card)
+                self.first_player_card =
+                    KPCard::random_sample_neq_other(self.second_player_card, rng)
             }
         }
     }
@@ -125,6 +128,22 @@ impl Game for KPState {
         }
     }
 
+    fn move_priors(&self) -> Vec<(Self::Move, f64)> {
+        let card = match self.current_player() {
+            KPPlayer::First => self.first_player_card,
+            KPPlayer::Second => self.second_player_card,
+        };
+        // Holding the King, betting or calling is almost always right
+        self.available_moves()
+            .into_iter()
+            .map(|m| match (card, m) {
+                (KPCard::King, KPMove::Bet | KPMove::Call) => (m, 0.8),
+                (KPCard::King, _) => (m, 0.2),
+                _ => (m, 0.5),
+            })
+            .collect()
+    }
+
     fn make_move(&mut self, mov: &Self::Move) {
         assert!(
             self.move_history.len() <= 2,
@@ -157,25 +176,27 @@ impl Game for KPState {
 }
 
 pub fn main() {
-    // playout_once(true);
+    // playout_once(ismcts_policy, true);
 
     let n = 10000;
     let mut results = 0.0;
+    let mut mo_results = 0.0;
     for _ in 0..n {
-        let result = playout_once(false);
-        results += result;
+        results += playout_once(ismcts_policy, false);
+        mo_results += playout_once(ismcts_mo_policy, false);
     }
 
     println!("ISMCTS average result: {:?}", results / n as f64);
+    println!("MO-ISMCTS average result: {:?}", mo_results / n as f64);
 }
 
-pub fn playout_once(verbose: bool) -> f64 {
+pub fn playout_once(policy: fn(&mut KPState) -> Option<KPMove>, verbose: bool) -> f64 {
     let mut state = KPState::random_starting_position();
     if verbose {
         dbg!(&state);
     }
 
-    let mov = ismcts_policy(&mut state.clone()).unwrap();
+    let mov = policy(&mut state.clone()).unwrap();
     if verbose {
         println!("ISMCTS move: {:?}", mov);
     }
@@ -187,7 +208,7 @@ pub fn playout_once(verbose: bool) -> f64 {
     }
     state.make_move(&mov);
 
-    if let Some(mov) = ismcts_policy(&mut state.clone()) {
+    if let Some(mov) = policy(&mut state.clone()) {
         if verbose {
             println!("ISMCTS move: {:?}", mov);
         }
@@ -212,7 +233,15 @@ pub fn playout_once(verbose: bool) -> f64 {
 }
 
 pub fn ismcts_policy(state: &mut KPState) -> Option<KPMove> {
-    let mut ismcts = IsmctsHandler::new(state.clone());
+    let ismcts = IsmctsHandler::new(state.clone());
+    ismcts.run_iterations(4, 10000 / 4);
+    ismcts.best_move()
+}
+
+/// Every move is public in Kuhn poker, so the trees see the same moves and this should play as
+/// well as `ismcts_policy`
+pub fn ismcts_mo_policy(state: &mut KPState) -> Option<KPMove> {
+    let ismcts = IsmctsHandlerMo::new(state.clone(), vec![KPPlayer::First, KPPlayer::Second]);
     ismcts.run_iterations(4, 10000 / 4);
     ismcts.best_move()
 }
@@ -279,9 +308,9 @@ impl KPCard {
         rand::random()
     }
 
-    pub fn random_sample_neq_other(other: Self) -> Self {
+    pub fn random_sample_neq_other<R: Rng + ?Sized>(other: Self, rng: &mut R) -> Self {
         loop {
-            let c: KPCard = rand::random();
+            let c: KPCard = rng.gen();
             if c != other {
                 return c;
             }
@@ -1,4 +1,5 @@
 use ismcts::*;
+use rand::Rng;
 use std::iter;
 
 #[derive(Clone, Copy, Debug, PartialEq)]
@@ -41,7 +42,11 @@ impl Game for NimState {
     type PlayerTag = NimPlayer;
     type MoveList = Vec<NimMove>;
 
-    fn randomize_determination(&mut self, _observer: Self::PlayerTag) {
+    fn randomize_determination<R: Rng + ?Sized>(
+        &mut self,
+        _observer: Self::PlayerTag,
+        _rng: &mut R,
+    ) {
         //No-op
     }
 
@@ -66,6 +71,10 @@ impl Game for NimState {
             .collect()
     }
 
+    fn num_available_moves(&self) -> usize {
+        self.heaps.iter().sum()
+    }
+
     fn make_move(&mut self, mov: &Self::Move) {
         if mov.heap >= self.heaps.len() {
             panic!("trying to move on out of bounds heap");
@@ -191,8 +200,9 @@ fn maintain_win() {
     };
 
     let mut ismcts = IsmctsHandler::new(game);
+    ismcts.config_mut().reward_bounds = Some((-1.0, 1.0));
     while ismcts.state().result(NimPlayer::First).is_none() {
-        ismcts.run_iterations(N_THREADS, ismcts.state().available_moves().len());
+        ismcts.run_iterations(N_THREADS, ismcts.state().num_available_moves());
 
         println!("{:?}", &ismcts.state());
         let perfect_mov = perfect_move(ismcts.state());
@@ -204,9 +214,9 @@ fn maintain_win() {
             ismcts.debug_children();
 
             println!("ISMCTS move: {:?}", mov);
-            ismcts.make_move(&mov);
+            ismcts.make_move(&mov).unwrap();
         } else {
-            ismcts.make_move(&perfect_mov);
+            ismcts.make_move_unchecked(&perfect_mov);
         }
         println!();
     }
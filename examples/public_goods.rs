@@ -0,0 +1,90 @@
+use ismcts::*;
+use rand::Rng;
+
+const PLAYERS: usize = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PgMove {
+    Contribute,
+    Keep,
+}
+
+/// Three players in turn either keep their coin or contribute it to a pot, which is doubled and
+/// shared equally. Everyone gains from each contribution, but the contributor gets back less than
+/// they put in, so every player should keep their coin.
+#[derive(Clone, Debug, Default)]
+pub struct PublicGoodsState {
+    moves: Vec<PgMove>,
+}
+
+impl Game for PublicGoodsState {
+    type Move = PgMove;
+    type PlayerTag = usize;
+    type MoveList = Vec<PgMove>;
+
+    fn randomize_determination<R: Rng + ?Sized>(
+        &mut self,
+        _observer: Self::PlayerTag,
+        _rng: &mut R,
+    ) {
+        // Every move is public
+    }
+
+    fn current_player(&self) -> Self::PlayerTag {
+        self.moves.len() % PLAYERS
+    }
+
+    fn next_player(&self) -> Self::PlayerTag {
+        (self.current_player() + 1) % PLAYERS
+    }
+
+    fn available_moves(&self) -> Self::MoveList {
+        if self.moves.len() < PLAYERS {
+            vec![PgMove::Contribute, PgMove::Keep]
+        } else {
+            Vec::new()
+        }
+    }
+
+    fn make_move(&mut self, mov: &Self::Move) {
+        self.moves.push(*mov);
+    }
+
+    fn result(&self, player: Self::PlayerTag) -> Option<f64> {
+        if self.moves.len() < PLAYERS {
+            return None;
+        }
+        let pot = self
+            .moves
+            .iter()
+            .filter(|&&m| m == PgMove::Contribute)
+            .count();
+        let kept = match self.moves[player] {
+            PgMove::Keep => 1.0,
+            PgMove::Contribute => 0.0,
+        };
+        Some(kept + 2.0 * pot as f64 / PLAYERS as f64)
+    }
+}
+
+pub fn main() {
+    let mut ismcts = IsmctsHandler::new(PublicGoodsState::default());
+    ismcts.config_mut().players = Some((0..PLAYERS).collect());
+    ismcts.run_iterations(1, 20000);
+
+    // Everyone contributing would pay each player 2, but the equilibrium pays each 1
+    println!("First player's best move: {:?}", ismcts.best_move());
+    for child in ismcts.root_children_sorted_by(|c| c.visit_count) {
+        let means: Vec<_> = child
+            .player_rewards
+            .iter()
+            .map(|r| format!("{:.2}", r / child.visit_count as f64))
+            .collect();
+        println!(
+            "{:?}: {} visits, mean rewards {}",
+            child.mov.unwrap(),
+            child.visit_count,
+            means.join(", ")
+        );
+    }
+}
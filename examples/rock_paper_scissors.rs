@@ -0,0 +1,106 @@
+use ismcts::*;
+use rand::Rng;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RpsMove {
+    Rock,
+    Paper,
+    Scissors,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RpsPlayer {
+    First,
+    Second,
+}
+
+/// Rock paper scissors where winning with rock pays double, played as one simultaneous round
+#[derive(Clone, Debug, Default)]
+pub struct RpsState {
+    moves: Vec<RpsMove>,
+}
+
+impl RpsState {
+    /// Payoff of playing `own` against `other`
+    fn payoff(own: RpsMove, other: RpsMove) -> f64 {
+        use RpsMove::*;
+        match (own, other) {
+            (Rock, Scissors) => 2.0,
+            (Paper, Rock) | (Scissors, Paper) => 1.0,
+            (Scissors, Rock) => -2.0,
+            (Rock, Paper) | (Paper, Scissors) => -1.0,
+            _ => 0.0,
+        }
+    }
+}
+
+impl Game for RpsState {
+    type Move = RpsMove;
+    type PlayerTag = RpsPlayer;
+    type MoveList = Vec<RpsMove>;
+
+    fn randomize_determination<R: Rng + ?Sized>(
+        &mut self,
+        _observer: Self::PlayerTag,
+        _rng: &mut R,
+    ) {
+        // Nothing is hidden once the round is over
+    }
+
+    fn current_player(&self) -> Self::PlayerTag {
+        match self.moves.len() {
+            0 => RpsPlayer::First,
+            _ => RpsPlayer::Second,
+        }
+    }
+
+    fn next_player(&self) -> Self::PlayerTag {
+        match self.current_player() {
+            RpsPlayer::First => RpsPlayer::Second,
+            RpsPlayer::Second => RpsPlayer::First,
+        }
+    }
+
+    fn is_simultaneous(&self) -> bool {
+        // The second player chooses at the same time as the first
+        self.moves.len() == 1
+    }
+
+    fn available_moves(&self) -> Self::MoveList {
+        if self.moves.len() < 2 {
+            vec![RpsMove::Rock, RpsMove::Paper, RpsMove::Scissors]
+        } else {
+            Vec::new()
+        }
+    }
+
+    fn make_move(&mut self, mov: &Self::Move) {
+        self.moves.push(*mov);
+    }
+
+    fn result(&self, player: Self::PlayerTag) -> Option<f64> {
+        match (self.moves.as_slice(), player) {
+            ([first, second], RpsPlayer::First) => Some(Self::payoff(*first, *second)),
+            ([first, second], RpsPlayer::Second) => Some(Self::payoff(*second, *first)),
+            _ => None,
+        }
+    }
+}
+
+pub fn main() {
+    let iterations = 100000;
+    let ismcts = IsmctsHandler::new(RpsState::default());
+    ismcts.run_iterations(1, iterations);
+
+    // Decoupled UCB only roughly finds the equilibrium mix, but the mean payoffs should all
+    // approach the game's value
+    println!("Equilibrium: Rock 25%, Paper 50%, Scissors 25%, mean payoff 0");
+    for (mov, visits, mean) in ismcts.move_statistics() {
+        println!(
+            "{:?}: played {:.1}%, mean payoff {:.3}",
+            mov,
+            100.0 * visits as f64 / iterations as f64,
+            mean
+        );
+    }
+}
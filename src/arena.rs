@@ -0,0 +1,177 @@
+use crate::*;
+
+/// A node of an `ArenaIsmcts` tree, referring to other nodes by index
+struct ArenaNode<G: Game> {
+    mov: Option<G::Move>,
+    parent: Option<usize>,
+    children: Vec<usize>,
+    player_just_moved: Option<G::PlayerTag>,
+    statistics: NodeStatistics,
+}
+
+/// Single threaded search which stores its tree in one `Vec` instead of allocating each node,
+/// so iterations don't pay for reference counting or locking.
+/// Supports plain UCB1 selection and random rollouts, without the options of `SearchConfig`.
+pub struct ArenaIsmcts<G: Game> {
+    root_state: G,
+    nodes: Vec<ArenaNode<G>>,
+    exploration: f64,
+    rng: StdRng,
+}
+
+impl<G: Game> ArenaIsmcts<G> {
+    pub fn new(root_state: G) -> Self {
+        Self::with_rng(root_state, StdRng::from_rng(thread_rng()).unwrap())
+    }
+
+    /// Like `new`, but searches are reproducible
+    pub fn with_seed(root_state: G, seed: u64) -> Self {
+        Self::with_rng(root_state, StdRng::seed_from_u64(seed))
+    }
+
+    fn with_rng(root_state: G, rng: StdRng) -> Self {
+        ArenaIsmcts {
+            root_state,
+            nodes: vec![ArenaNode {
+                mov: None,
+                parent: None,
+                children: Vec::new(),
+                player_just_moved: None,
+                statistics: Default::default(),
+            }],
+            exploration: std::f64::consts::SQRT_2,
+            rng,
+        }
+    }
+
+    pub fn run_iterations(&mut self, n_iterations: usize) {
+        for _ in 0..n_iterations {
+            self.one_iteration();
+        }
+    }
+
+    fn one_iteration(&mut self) {
+        let mut state = self.root_state.clone();
+        let observer = state.current_player();
+        state.randomize_determination(observer, &mut self.rng);
+
+        // Select
+        let mut node = 0;
+        let mut untried_moves;
+        loop {
+            while state.is_chance_node() {
+                state.sample_chance(&mut self.rng);
+            }
+            let available_moves: Vec<_> = state
+                .prune_moves(state.available_moves())
+                .into_iter()
+                .collect();
+            let children = &self.nodes[node].children;
+            untried_moves = available_moves
+                .iter()
+                .filter(|m| {
+                    !children
+                        .iter()
+                        .any(|&c| self.nodes[c].mov.as_ref() == Some(*m))
+                })
+                .cloned()
+                .collect::<Vec<_>>();
+            let legal_children: Vec<usize> = children
+                .iter()
+                .copied()
+                .filter(|&c| available_moves.contains(self.nodes[c].mov.as_ref().unwrap()))
+                .collect();
+            if available_moves.is_empty() || !untried_moves.is_empty() {
+                // Siblings of the move about to be expanded were also available
+                for &c in &legal_children {
+                    self.nodes[c].statistics.availability_count += 1;
+                }
+                break;
+            }
+            let choice = *legal_children
+                .iter()
+                .max_by_key(|&&c| {
+                    let statistics = &self.nodes[c].statistics;
+                    let score = statistics
+                        .ucb1_with_availability(statistics.availability_count, self.exploration);
+                    let order = move_order(&available_moves, self.nodes[c].mov.as_ref().unwrap());
+                    (OrderedFloat::from(score), order)
+                })
+                .unwrap();
+            for &c in &legal_children {
+                self.nodes[c].statistics.availability_count += 1;
+            }
+            node = choice;
+            state.make_move(self.nodes[node].mov.as_ref().unwrap());
+        }
+
+        // Expand
+        if let Some(m) = choose_expansion(&state, &untried_moves, &mut self.rng) {
+            let player_just_moved = state.current_player();
+            state.make_move(&m);
+            let child = self.nodes.len();
+            self.nodes.push(ArenaNode {
+                mov: Some(m),
+                parent: Some(node),
+                children: Vec::new(),
+                player_just_moved: Some(player_just_moved),
+                statistics: NodeStatistics {
+                    availability_count: 1,
+                    ..Default::default()
+                },
+            });
+            self.nodes[node].children.push(child);
+            node = child;
+        }
+
+        // Simulate
+        state.random_rollout(&mut self.rng, DEFAULT_MAX_ROLLOUT_MOVES);
+
+        // Backpropagate
+        let mut current = Some(node);
+        while let Some(index) = current {
+            let arena_node = &mut self.nodes[index];
+            let statistics = &mut arena_node.statistics;
+            statistics.visit_count += 1;
+            if let Some(player) = arena_node.player_just_moved {
+                let r = state.evaluate(player);
+                statistics.reward += r;
+                statistics.reward_sq += r * r;
+            }
+            current = arena_node.parent;
+        }
+    }
+
+    /// Most visited move at the root
+    pub fn best_move(&self) -> Option<G::Move> {
+        let available_moves: Vec<_> = self.root_state.available_moves().into_iter().collect();
+        self.nodes[0]
+            .children
+            .iter()
+            .max_by_key(|&&c| {
+                let node = &self.nodes[c];
+                (
+                    node.statistics.visit_count,
+                    move_order(&available_moves, node.mov.as_ref().unwrap()),
+                )
+            })
+            .map(|&c| self.nodes[c].mov.clone().unwrap())
+    }
+
+    /// Visits of each move at the root
+    pub fn move_visits(&self) -> Vec<(G::Move, usize)> {
+        self.nodes[0]
+            .children
+            .iter()
+            .map(|&c| {
+                let node = &self.nodes[c];
+                (node.mov.clone().unwrap(), node.statistics.visit_count)
+            })
+            .collect()
+    }
+
+    /// Number of nodes in the tree, including the root
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+}
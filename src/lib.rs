@@ -1,19 +1,29 @@
+#[cfg(all(feature = "threads", not(target_arch = "wasm32")))]
 use crossbeam::thread;
 use ordered_float::OrderedFloat;
 use rand::prelude::*;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::marker::{Send, Sync};
-use std::sync::{Arc, RwLock, Weak};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, LockResult, Mutex, OnceLock, PoisonError, RwLock, Weak};
 use std::time::{Duration, Instant};
 
+mod arena;
+mod mo;
+#[cfg(feature = "serde")]
+mod serialization;
+
+pub use arena::ArenaIsmcts;
+pub use mo::IsmctsHandlerMo;
 #[cfg(test)]
 mod tests;
 
 pub trait Game: Clone + Send + Sync {
     type Move: Clone + PartialEq + Send + Sync + std::fmt::Debug;
-    type PlayerTag: Clone + Copy + Send + Sync + std::fmt::Debug;
+    type PlayerTag: Clone + Copy + PartialEq + Send + Sync + std::fmt::Debug;
     type MoveList: Clone + std::iter::IntoIterator<Item = Self::Move>;
 
-    fn randomize_determination(&mut self, observer: Self::PlayerTag);
+    fn randomize_determination<R: Rng + ?Sized>(&mut self, observer: Self::PlayerTag, rng: &mut R);
 
     fn current_player(&self) -> Self::PlayerTag;
 
@@ -21,19 +31,702 @@ pub trait Game: Clone + Send + Sync {
 
     fn available_moves(&self) -> Self::MoveList;
 
+    /// Number of moves `available_moves` would return, for games that can count them without building the list
+    fn num_available_moves(&self) -> usize {
+        self.available_moves().into_iter().count()
+    }
+
     fn make_move(&mut self, mov: &Self::Move);
 
+    /// Reward for `player` once the game is over. Each node is scored by the reward of the
+    /// player who moved into it, so every player maximizes their own reward (max^n), which
+    /// also holds for games with more than two players or that aren't zero sum.
     fn result(&self, player: Self::PlayerTag) -> Option<f64>;
 
-    fn random_rollout(&mut self) {
-        let mut rng = thread_rng();
-        while self.result(self.current_player()).is_none() {
-            let mov = self.available_moves().into_iter().choose(&mut rng);
-            if let Some(m) = mov {
-                self.make_move(&m);
-            } else {
-                break;
+    /// Win, loss or draw for `player` once the game is over, for games with such results.
+    /// `result` should agree with it, e.g. by returning `Outcome::reward`.
+    fn outcome(&self, _player: Self::PlayerTag) -> Option<Outcome> {
+        None
+    }
+
+    /// Estimate of `result` used when a simulation is cut short before reaching a terminal state
+    fn evaluate(&self, player: Self::PlayerTag) -> f64 {
+        self.result(player).unwrap_or_default()
+    }
+
+    /// Identifies a position so that search trees can be matched across games.
+    /// Games which don't implement this opt out of tree reuse features.
+    fn state_hash(&self) -> Option<u64> {
+        None
+    }
+
+    /// Overwrite this state with `source`. Games with expensive clones can override this
+    /// to reuse existing allocations when `SearchConfig::reuse_state_buffers` is set.
+    fn reset_to(&mut self, source: &Self) {
+        self.clone_from(source);
+    }
+
+    /// Whether `unmake_move` is implemented. If so, each search thread keeps a single state and
+    /// undoes every iteration's moves instead of starting from a copy of the root state. The
+    /// next iteration's `randomize_determination` is then given the last determinization, and
+    /// `sample_chance` isn't undone, so games with chance nodes should leave this false.
+    fn can_unmake_moves(&self) -> bool {
+        false
+    }
+
+    /// Undo `mov`, the last move made. Only called when `can_unmake_moves` is true.
+    fn unmake_move(&mut self, _mov: &Self::Move) {
+        panic!("unmake_move is only called if can_unmake_moves returns true")
+    }
+
+    /// How `observer` sees `mov` when the current player makes it, for `IsmctsHandlerMo`.
+    /// Moves which `observer` can't tell apart should be mapped to the same move.
+    fn observe_move(&self, mov: &Self::Move, _observer: Self::PlayerTag) -> Self::Move {
+        mov.clone()
+    }
+
+    /// Whether the current player moves at the same time as the player before them, i.e.
+    /// without knowing their move. A simultaneous round is a move made while this is false
+    /// followed by every move made while it is true, and the state should only reveal the
+    /// round's moves once it's over. Search chooses each of the later moves from statistics
+    /// summed over all the earlier moves of the round (decoupled UCB).
+    fn is_simultaneous(&self) -> bool {
+        false
+    }
+
+    /// Whether the next change to the state is random instead of a player's move, e.g. a die
+    /// roll. Search samples it with `sample_chance` whenever it's reached rather than fixing it
+    /// in `randomize_determination`, and keeps no node for it, so the nodes below average over
+    /// its outcomes. A game returning true must also implement `sample_chance`.
+    fn is_chance_node(&self) -> bool {
+        false
+    }
+
+    /// Apply a random outcome at a chance node. Only called while `is_chance_node` is true,
+    /// so it must be implemented together with it.
+    fn sample_chance<R: Rng + ?Sized>(&mut self, _rng: &mut R) {
+        panic!("Game::is_chance_node returned true, but Game::sample_chance isn't implemented")
+    }
+
+    /// Move to play during rollouts, e.g. from a cheap heuristic.
+    /// Returning `None` falls back to a uniformly random move.
+    fn rollout_policy<R: Rng + ?Sized>(&self, _rng: &mut R) -> Option<Self::Move> {
+        None
+    }
+
+    /// Moves among `moves`, the available moves, which search should consider, e.g. leaving
+    /// out moves known to be dominated. Pruned moves are never expanded, but stay legal for
+    /// `make_move`. At least one move should be kept whenever any are available, since search
+    /// treats a state with none as the end of the game.
+    fn prune_moves(&self, moves: Self::MoveList) -> Self::MoveList {
+        moves
+    }
+
+    /// How early to expand `mov` relative to the other untried moves. Expansion picks randomly
+    /// among the untried moves of the highest priority.
+    fn move_priority(&self, _mov: &Self::Move) -> i32 {
+        0
+    }
+
+    /// Prior probability of each available move, used by `Selection::Puct`
+    fn move_priors(&self) -> Vec<(Self::Move, f64)> {
+        let moves: Vec<_> = self.available_moves().into_iter().collect();
+        let prior = 1.0 / moves.len() as f64;
+        moves.into_iter().map(|m| (m, prior)).collect()
+    }
+
+    /// Play until the game ends, or until `max_moves` moves and chance outcomes have been
+    /// played, following `rollout_policy` and sampling chance nodes, see `play_rollout`
+    fn random_rollout<R: Rng + ?Sized>(&mut self, rng: &mut R, max_moves: usize) {
+        play_rollout(self, rng, max_moves)
+    }
+}
+
+/// Moves after which a rollout is given up on and scored with `Game::evaluate`, so that a game
+/// which never ends can't hang the search
+pub const DEFAULT_MAX_ROLLOUT_MOVES: usize = 10_000;
+
+/// Result of a finished game for one player, see `Game::outcome`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    Win,
+    Loss,
+    Draw,
+}
+
+impl Outcome {
+    /// Reward of 1 for a win, 0 for a loss and 0.5 for a draw
+    pub fn reward(self) -> f64 {
+        match self {
+            Outcome::Win => 1.0,
+            Outcome::Loss => 0.0,
+            Outcome::Draw => 0.5,
+        }
+    }
+}
+
+pub type AggregationFn = Arc<dyn Fn(&[f64]) -> f64 + Send + Sync>;
+
+/// How a node's value is derived during backpropagation
+#[derive(Clone, Default)]
+pub enum BackpropAggregation {
+    /// Every simulation result is accumulated into a running mean
+    #[default]
+    Average,
+    /// A node's value is the minimax value of its visited children: the value of the child best
+    /// for the player choosing between them, seen by the player who moved into the node. A node
+    /// without visited children takes the latest simulation result. Assumes a two-player game
+    /// with rewards in the range 0 to 1 (see `SearchConfig::reward_bounds`), so that one
+    /// player's value is 1 minus the other's.
+    Max,
+    /// Called with the leaf result first, followed by the values of the node's visited children.
+    /// Each value is seen by the player who moved into its own node, so the children's values
+    /// are usually from the opponent's point of view.
+    Custom(AggregationFn),
+}
+
+impl std::fmt::Debug for BackpropAggregation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BackpropAggregation::Average => write!(f, "Average"),
+            BackpropAggregation::Max => write!(f, "Max"),
+            BackpropAggregation::Custom(_) => write!(f, "Custom(..)"),
+        }
+    }
+}
+
+impl BackpropAggregation {
+    /// Value of a node entered by `player`, given the latest simulation `result` for `player`
+    /// and the player who moved into and the value of each visited child
+    fn aggregate<P: PartialEq>(
+        &self,
+        player: P,
+        result: f64,
+        children: &[(P, f64)],
+    ) -> Option<f64> {
+        match self {
+            BackpropAggregation::Average => None,
+            BackpropAggregation::Max => {
+                let best = children
+                    .iter()
+                    .max_by_key(|(_, value)| OrderedFloat::from(*value));
+                Some(match best {
+                    None => result,
+                    Some((chooser, value)) if *chooser == player => *value,
+                    Some((_, value)) => 1.0 - value,
+                })
+            }
+            BackpropAggregation::Custom(f) => {
+                let mut values = vec![result];
+                values.extend(children.iter().map(|(_, value)| value));
+                Some(f(&values))
+            }
+        }
+    }
+}
+
+/// Which count UCB1 uses as the number of opportunities a child had to be selected
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum UcbAvailability {
+    /// The child's own availability count
+    #[default]
+    Child,
+    /// The parent's visit count, shared by all siblings
+    Parent,
+}
+
+impl UcbAvailability {
+    /// Availability passed to `SelectionPolicy::score` for a child with `statistics`
+    fn count(self, statistics: &NodeStatistics, parent_visits: usize) -> usize {
+        match self {
+            UcbAvailability::Child => statistics.availability_count,
+            UcbAvailability::Parent => parent_visits,
+        }
+    }
+}
+
+/// Penalty applied to children which other threads are currently searching through, so that
+/// concurrent iterations spread out instead of all following the same path
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum VirtualLoss {
+    #[default]
+    Disabled,
+    /// Each in-flight iteration counts as this many lost simulations
+    Constant(f64),
+    /// Each in-flight iteration counts as `base * (n_threads - 1) / sqrt(1 + visits)` lost
+    /// simulations, so it has no effect single threaded and fades as a child's statistics firm up
+    Adaptive { base: f64 },
+}
+
+impl VirtualLoss {
+    /// Number of lost simulations added per in-flight iteration through a child with `visit_count` visits
+    pub fn magnitude(&self, visit_count: usize, n_threads: usize) -> f64 {
+        match *self {
+            VirtualLoss::Disabled => 0.0,
+            VirtualLoss::Constant(loss) => loss,
+            VirtualLoss::Adaptive { base } => {
+                base * n_threads.saturating_sub(1) as f64 / (1.0 + visit_count as f64).sqrt()
+            }
+        }
+    }
+}
+
+/// How many children a node gains when an iteration expands it
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ExpansionPolicy {
+    /// One untried move
+    #[default]
+    Single,
+    /// Every untried legal move, so wide nodes fill out on their first visit
+    Full,
+}
+
+/// How `IsmctsHandler::best_move_by` chooses among the root's children
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BestMoveCriterion {
+    /// The most visited child, as `best_move` does
+    MostVisits,
+    /// The highest mean reward among children with at least `min_visits` visits
+    HighestReward { min_visits: usize },
+    /// The most visited child only if it also has the highest mean reward.
+    /// Otherwise `None`, meaning the search should be continued until they agree.
+    RobustMax,
+}
+
+/// How `IsmctsHandler::run_iterations_root_parallel` chooses a move from the threads' trees.
+/// The trees are summed into the handler's tree whichever is used.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MergeStrategy {
+    /// The most visited move of the summed trees, as `best_move` then returns
+    #[default]
+    SumStatistics,
+    /// The move which is the most visited in the most trees, each tree having one vote
+    MajorityVote,
+    /// Like `MajorityVote`, but each tree's vote is weighted by the share of the tree's visits
+    /// its most visited move received
+    WeightedByVisits,
+}
+
+/// Formula for scoring children during selection
+#[derive(Clone, Debug, Default)]
+pub enum Selection {
+    /// `mean + c * sqrt(ln(availability) / visits)`
+    #[default]
+    Ucb1,
+    /// `mean + c * prior * sqrt(availability) / (1 + visits)`, with priors from `Game::move_priors`
+    Puct,
+    /// UCB1 with the exploration term bounded by the variance of each child's rewards.
+    /// Assumes results between 0 and 1, and doesn't use `SearchConfig::exploration`.
+    Ucb1Tuned,
+    /// A formula of one's own, given priors from `Game::move_priors` like `Puct`.
+    /// `SearchConfig::exploration` is left to the policy.
+    Custom(Arc<dyn SelectionPolicy>),
+}
+
+impl PartialEq for Selection {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Selection::Custom(a), Selection::Custom(b)) => Arc::ptr_eq(a, b),
+            _ => std::mem::discriminant(self) == std::mem::discriminant(other),
+        }
+    }
+}
+
+impl Eq for Selection {}
+
+impl Selection {
+    /// Whether new children get their prior from `Game::move_priors` rather than 1
+    fn uses_priors(&self) -> bool {
+        matches!(self, Selection::Puct | Selection::Custom(_))
+    }
+
+    /// `SelectionPolicy::score_with_virtual_loss` of this formula with `exploration`
+    fn score_with_virtual_loss(
+        &self,
+        exploration: f64,
+        statistics: &NodeStatistics,
+        availability: usize,
+        virtual_loss: f64,
+        prior: f64,
+    ) -> f64 {
+        let score = |policy: &dyn SelectionPolicy| {
+            policy.score_with_virtual_loss(statistics, availability, virtual_loss, prior)
+        };
+        match self {
+            Selection::Ucb1 => score(&Ucb1 { exploration }),
+            Selection::Ucb1Tuned => score(&Ucb1Tuned),
+            Selection::Puct => score(&Puct { exploration }),
+            Selection::Custom(policy) => score(policy.as_ref()),
+        }
+    }
+}
+
+/// Formula for scoring children during selection, which picks the highest scoring child
+pub trait SelectionPolicy: std::fmt::Debug + Send + Sync {
+    /// Score of a child with `statistics`, where `availability` counts the times it could have
+    /// been selected or its parent's visits, depending on `SearchConfig::ucb_availability`
+    fn score(&self, statistics: &NodeStatistics, availability: usize) -> f64;
+
+    /// Like `score`, also given the child's `Game::move_priors` probability and the virtual
+    /// loss of each of its in-flight iterations, which are ignored by default
+    fn score_with_virtual_loss(
+        &self,
+        statistics: &NodeStatistics,
+        availability: usize,
+        _virtual_loss: f64,
+        _prior: f64,
+    ) -> f64 {
+        self.score(statistics, availability)
+    }
+}
+
+/// `SelectionPolicy` for `Selection::Ucb1`. Its exploration term is scaled by the child's prior,
+/// which is 1 unless `IsmctsHandler::add_root_noise` was used.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Ucb1 {
+    pub exploration: f64,
+}
+
+impl SelectionPolicy for Ucb1 {
+    fn score(&self, statistics: &NodeStatistics, availability: usize) -> f64 {
+        statistics.ucb1_with_availability(availability, self.exploration)
+    }
+
+    fn score_with_virtual_loss(
+        &self,
+        statistics: &NodeStatistics,
+        availability: usize,
+        virtual_loss: f64,
+        prior: f64,
+    ) -> f64 {
+        statistics.ucb1_with_virtual_loss(availability, virtual_loss, self.exploration * prior)
+    }
+}
+
+/// `SelectionPolicy` for `Selection::Ucb1Tuned`
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Ucb1Tuned;
+
+impl SelectionPolicy for Ucb1Tuned {
+    fn score(&self, statistics: &NodeStatistics, availability: usize) -> f64 {
+        statistics.ucb1_tuned_with_virtual_loss(availability, 0.0)
+    }
+
+    fn score_with_virtual_loss(
+        &self,
+        statistics: &NodeStatistics,
+        availability: usize,
+        virtual_loss: f64,
+        _prior: f64,
+    ) -> f64 {
+        statistics.ucb1_tuned_with_virtual_loss(availability, virtual_loss)
+    }
+}
+
+/// `SelectionPolicy` for `Selection::Puct`. Search always passes the prior to
+/// `score_with_virtual_loss`, while `score` has none to go on and assumes 1.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Puct {
+    pub exploration: f64,
+}
+
+impl SelectionPolicy for Puct {
+    fn score(&self, statistics: &NodeStatistics, availability: usize) -> f64 {
+        statistics.puct_with_virtual_loss(availability, 0.0, self.exploration, 1.0)
+    }
+
+    fn score_with_virtual_loss(
+        &self,
+        statistics: &NodeStatistics,
+        availability: usize,
+        virtual_loss: f64,
+        prior: f64,
+    ) -> f64 {
+        statistics.puct_with_virtual_loss(availability, virtual_loss, self.exploration, prior)
+    }
+}
+
+pub type RolloutFn<G> = Arc<dyn Fn(&G, &mut StdRng) -> Option<<G as Game>::Move> + Send + Sync>;
+
+/// How rollouts choose their moves. Whenever no move is chosen, a random move is played.
+#[derive(Default)]
+pub enum RolloutPolicy<G: Game> {
+    /// `Game::rollout_policy`
+    #[default]
+    Game,
+    /// Called with the state and the search's random number generator
+    Custom(RolloutFn<G>),
+}
+
+impl<G: Game> RolloutPolicy<G> {
+    fn choose(&self, state: &G, rng: &mut StdRng) -> Option<G::Move> {
+        match self {
+            RolloutPolicy::Game => state.rollout_policy(rng),
+            RolloutPolicy::Custom(f) => f(state, rng),
+        }
+    }
+}
+
+impl<G: Game> Clone for RolloutPolicy<G> {
+    fn clone(&self) -> Self {
+        match self {
+            RolloutPolicy::Game => RolloutPolicy::Game,
+            RolloutPolicy::Custom(f) => RolloutPolicy::Custom(Arc::clone(f)),
+        }
+    }
+}
+
+impl<G: Game> std::fmt::Debug for RolloutPolicy<G> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RolloutPolicy::Game => write!(f, "Game"),
+            RolloutPolicy::Custom(_) => write!(f, "Custom(..)"),
+        }
+    }
+}
+
+/// Limit on how many children a node may expand, growing with its visits
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ProgressiveWidening {
+    pub k: f64,
+    pub alpha: f64,
+}
+
+impl ProgressiveWidening {
+    /// `ceil(k * visit_count^alpha)`
+    pub fn max_children(&self, visit_count: usize) -> usize {
+        (self.k * (visit_count as f64).powf(self.alpha)).ceil() as usize
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SearchConfig<G: Game> {
+    /// Seed for the random number generators of searches, see `IsmctsHandler::with_seed`
+    pub seed: Option<u64>,
+    pub selection: Selection,
+    /// Weight `c` of the exploration term, such as UCB1's `c * sqrt(ln(availability) / visits)`,
+    /// which should be scaled to the range of `Game::result`
+    pub exploration: f64,
+    pub backprop: BackpropAggregation,
+    pub ucb_availability: UcbAvailability,
+    pub rollout: RolloutPolicy<G>,
+    /// Without a `rollout` policy move, sample this many moves at each rollout step and play the
+    /// one with the highest `Game::move_priority`, breaking ties randomly, so that the heuristic
+    /// is only evaluated for a few of a large number of moves. `Some(0)` and `None` play a
+    /// uniformly random move.
+    pub rollout_move_sample_size: Option<usize>,
+    /// Track an exponential moving average of rewards with this weight on the newest result,
+    /// instead of the mean over all simulations
+    pub learning_rate: Option<f64>,
+    /// Reset a per-thread state buffer with `Game::reset_to` each iteration instead of cloning the root state
+    pub reuse_state_buffers: bool,
+    /// Abandon a simulation taking longer than this and score it with `Game::evaluate`
+    pub iteration_timeout: Option<Duration>,
+    /// Stop rollouts after this many moves and score them with `Game::evaluate`
+    pub max_rollout_depth: Option<usize>,
+    /// Safeguard against games which never end, see `DEFAULT_MAX_ROLLOUT_MOVES`. Chance outcomes
+    /// count as moves, and plain rollouts pass it on to `Game::random_rollout`.
+    pub max_rollout_moves: usize,
+    /// How deep to look for a node matching `Game::state_hash` when making an unexplored move
+    pub reroot_search_depth: usize,
+    /// Number of simulations averaged into a newly expanded node's first visit, so that its
+    /// value is less noisy when it starts competing in selection
+    pub warmup_rollouts: usize,
+    /// Start every iteration from this state instead of randomizing the hidden information,
+    /// e.g. to compare against perfect information play when the true state is known
+    pub fixed_determination: Option<G>,
+    pub virtual_loss: VirtualLoss,
+    /// Count the terminal results seen by the searching player, see `IsmctsHandler::result_distribution`
+    pub record_result_distribution: bool,
+    /// Score untried moves like the worst visited sibling instead of always expanding them
+    /// first, so that search exploits known good moves sooner in move spaces full of blunders
+    pub pessimistic_expansion: bool,
+    /// First play urgency: score untried moves as this instead of always expanding them first,
+    /// so a child whose selection score is higher is revisited instead. Takes precedence over
+    /// `pessimistic_expansion`.
+    pub fpu: Option<f64>,
+    pub expansion: ExpansionPolicy,
+    /// Only try new moves at a node while it has fewer legal children than the limit allows,
+    /// for games with more moves than can all be expanded
+    pub progressive_widening: Option<ProgressiveWidening>,
+    /// Blend all-moves-as-first (RAVE) values into selection with weight `sqrt(k / (3 * visits + k))`
+    /// for this `k`, which speeds up early convergence in games where a move's value doesn't
+    /// depend much on when it's played
+    pub rave: Option<f64>,
+    /// Share statistics between nodes whose positions have the same `Game::state_hash`,
+    /// so that different move orders reaching the same position learn together. A shared node
+    /// counts the visits through each of its parents, so a parent's visits no longer add up
+    /// over its children, see `NodeStatistics::leaf_visits`.
+    pub transposition_table: bool,
+    /// How often `IsmctsHandler::run_until_confident` checks whether the best move is decided
+    pub confidence_check_interval: Duration,
+    /// Prune the least visited subtrees beneath the root's children down to three quarters of
+    /// this many nodes whenever the tree grows larger. The statistics of pruned nodes are lost,
+    /// and their moves are expanded afresh if search returns to them.
+    pub max_nodes: Option<usize>,
+    /// Only check the clock every this many iterations of a timed search, since reading it
+    /// can cost as much as an iteration of a very cheap game
+    pub time_check_every: usize,
+    /// Scale a simulation's reward by this for each move between a node and the node the
+    /// simulation started from, so that nodes weight the results nearest to them most
+    pub discount: f64,
+    /// Number of rollouts from the leaf reached by each iteration, each after determinizing the
+    /// leaf again, whose mean result is backed up
+    pub determinizations_per_iter: usize,
+    /// Number of rollouts from the leaf reached by each iteration, run on that many threads at
+    /// once, whose mean result is backed up. Less noisy iterations without sharing the tree
+    /// between threads. Without the `threads` feature the rollouts are run one after another.
+    ///
+    /// Each iteration starts `leaf_parallel - 1` scoped threads rather than reusing a pool, which
+    /// costs around 20 microseconds per thread on a typical desktop. That is more than a whole
+    /// iteration of many small games, so this only pays off when rollouts are long.
+    pub leaf_parallel: usize,
+    /// Lowest and highest possible rewards, to scale rewards into the range 0 to 1 which the
+    /// exploration terms of UCB1 and PUCT are tuned for when `exploration` is left at its default.
+    /// The lowest must be below the highest, or searching panics.
+    pub reward_bounds: Option<(f64, f64)>,
+    /// Also sum each node's rewards for every one of these players, to see how a general-sum
+    /// game turns out for everyone, see `ChildInfo::player_rewards`. Selection scores a node by
+    /// the entry of the player who moved into it, which is its usual reward.
+    pub players: Option<Vec<G::PlayerTag>>,
+    /// Only update the node a simulation started from and this many of its ancestors, instead
+    /// of every node up to the root. The root's children then only count iterations that
+    /// expanded a node close enough to them, so their visits stop adding up to the iterations
+    /// run and `best_move` reflects only the shallow part of the search.
+    pub backup_depth: Option<usize>,
+    /// Two-player zero-sum game whose `result` and `evaluate` only need to be right for this
+    /// player. Every other player is rewarded with 1 minus its reward, so rewards must lie
+    /// between 0 and 1, or between `reward_bounds` if set.
+    pub zero_sum: Option<G::PlayerTag>,
+    /// Waited on by each iteration just before it adds children, so that tests can make threads
+    /// expand the same node at the same time
+    #[cfg(test)]
+    pub(crate) expansion_barrier: Option<Arc<std::sync::Barrier>>,
+}
+
+impl<G: Game> SearchConfig<G> {
+    /// `state`'s result for `player`, derived from the `zero_sum` player's result if set
+    pub fn result(&self, state: &G, player: G::PlayerTag) -> Option<f64> {
+        let perspective = self.zero_sum.unwrap_or(player);
+        let result = state.result(perspective)?;
+        Some(if perspective == player {
+            result
+        } else {
+            match self.reward_bounds {
+                Some((low, high)) => low + high - result,
+                None => 1.0 - result,
             }
+        })
+    }
+
+    /// Panic unless `reward_bounds`, which `IsmctsHandler::config_mut` can set without the
+    /// builder's check, has its low below its high. Checked once when a search starts.
+    fn check_reward_bounds(&self) {
+        if let Some((low, high)) = self.reward_bounds {
+            assert!(
+                low < high,
+                "reward_bounds must have low < high, got ({}, {})",
+                low,
+                high
+            );
+        }
+    }
+
+    /// Score which selection ranks a child by: `selection`'s formula, mixed with the child's
+    /// all-moves-as-first mean if `rave` is set
+    fn selection_score(
+        &self,
+        statistics: &NodeStatistics,
+        availability: usize,
+        virtual_loss: f64,
+        prior: f64,
+    ) -> f64 {
+        let score = self.selection.score_with_virtual_loss(
+            self.exploration,
+            statistics,
+            availability,
+            virtual_loss,
+            prior,
+        );
+        match self.rave {
+            Some(equivalence) => statistics.rave_score(score, equivalence),
+            None => score,
+        }
+    }
+
+    /// Reward of `player` at `leaf`, scaled by `reward_bounds`
+    fn leaf_reward(&self, leaf: &G, player: G::PlayerTag) -> f64 {
+        let perspective = self.zero_sum.unwrap_or(player);
+        let reward = leaf.evaluate(perspective);
+        let scaled = match self.reward_bounds {
+            Some((low, high)) => (reward - low) / (high - low),
+            None => reward,
+        };
+        if perspective == player {
+            scaled
+        } else {
+            1.0 - scaled
+        }
+    }
+
+    /// Mean reward of `player` over `leaves`, scaled by `reward_bounds`
+    fn mean_reward(&self, leaves: &[G], player: G::PlayerTag) -> f64 {
+        leaves
+            .iter()
+            .map(|leaf| self.leaf_reward(leaf, player))
+            .sum::<f64>()
+            / leaves.len() as f64
+    }
+
+    /// Mean of the squares of the rewards averaged by `mean_reward`
+    fn mean_squared_reward(&self, leaves: &[G], player: G::PlayerTag) -> f64 {
+        leaves
+            .iter()
+            .map(|leaf| self.leaf_reward(leaf, player).powi(2))
+            .sum::<f64>()
+            / leaves.len() as f64
+    }
+}
+
+impl<G: Game> Default for SearchConfig<G> {
+    fn default() -> Self {
+        SearchConfig {
+            seed: None,
+            selection: Default::default(),
+            exploration: std::f64::consts::SQRT_2,
+            backprop: Default::default(),
+            ucb_availability: Default::default(),
+            rollout: Default::default(),
+            rollout_move_sample_size: None,
+            learning_rate: None,
+            reuse_state_buffers: false,
+            iteration_timeout: None,
+            max_rollout_depth: None,
+            max_rollout_moves: DEFAULT_MAX_ROLLOUT_MOVES,
+            reroot_search_depth: 4,
+            warmup_rollouts: 1,
+            fixed_determination: None,
+            virtual_loss: Default::default(),
+            record_result_distribution: false,
+            pessimistic_expansion: false,
+            fpu: None,
+            expansion: Default::default(),
+            progressive_widening: None,
+            rave: None,
+            transposition_table: false,
+            confidence_check_interval: Duration::from_millis(10),
+            max_nodes: None,
+            time_check_every: 1,
+            discount: 1.0,
+            determinizations_per_iter: 1,
+            // No extra threads, which would be started afresh every iteration
+            leaf_parallel: 1,
+            reward_bounds: None,
+            players: None,
+            backup_depth: None,
+            zero_sum: None,
+            #[cfg(test)]
+            expansion_barrier: None,
         }
     }
 }
@@ -41,174 +734,2791 @@ pub trait Game: Clone + Send + Sync {
 struct Node<G: Game> {
     /// Move which entered this node
     mov: Option<G::Move>,
-    parent: Option<Weak<Node<G>>>,
-    children: RwLock<Vec<Arc<Node<G>>>>,
+    /// Severed when this node becomes the root, so that backprop stops here
+    parent: TreeLock<Option<Weak<Node<G>>>>,
+    children: TreeLock<Vec<Arc<Node<G>>>>,
     player_just_moved: Option<G::PlayerTag>,
-    statistics: RwLock<NodeStatistics>,
+    /// Prior probability of `mov` from `Game::move_priors`, only set for `Selection::Puct` and
+    /// `Selection::Custom`.
+    /// Noise from `IsmctsHandler::add_root_noise` is mixed in when the root is searched.
+    prior: AtomicF64,
+    /// Shared with transposing nodes when `SearchConfig::transposition_table` is set
+    statistics: SharedStatistics,
+    /// Whether the game was found to be over at this node, so its result needs no simulation
+    terminal: AtomicBool,
+    /// Set, under the children's lock, once `SearchConfig::max_nodes` removes this node from the
+    /// tree, so that children added by iterations still searching it aren't counted
+    pruned: AtomicBool,
 }
 
-#[derive(Debug, Default)]
-struct NodeStatistics {
+type SharedStatistics = Arc<AtomicStatistics>;
+
+/// Search statistics of a node, as seen by a `SelectionPolicy`
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NodeStatistics {
     visit_count: usize,
+    /// Iterations which chose a move at the parent, by selection or expansion, while this node
+    /// existed and its move was legal, including the iteration which added it
     availability_count: usize,
     reward: f64,
+    reward_sq: f64,
+    /// Value computed by a non-additive `BackpropAggregation`, replacing the mean reward
+    value: Option<f64>,
+    /// Iterations which selected this node and haven't backpropagated yet
+    in_flight: usize,
+    /// Visits from iterations whose simulation started at this node. While no search is running,
+    /// `visit_count` is exactly this plus the children's visit counts, unless
+    /// `SearchConfig::transposition_table` shares statistics between nodes,
+    /// `SearchConfig::backup_depth` stops updates short of the root or `SearchConfig::max_nodes`
+    /// prunes visited children.
+    leaf_visits: usize,
+    /// Simulations in which this node's move was played by the same player at any later point
+    amaf_visits: usize,
+    amaf_reward: f64,
+    /// Visits whose simulations ended in a draw by `Game::outcome`, fractional when a visit
+    /// averages over several simulations
+    draws: f64,
+    /// Summed rewards of each of `SearchConfig::players`, empty if unset
+    player_rewards: Vec<f64>,
 }
 
 impl NodeStatistics {
-    pub fn ucb1(&self) -> f64 {
-        (self.reward / self.visit_count as f64)
-            + (2.0 * (self.availability_count as f64).ln() / self.visit_count as f64).sqrt()
+    pub fn visit_count(&self) -> usize {
+        self.visit_count
+    }
+
+    /// Iterations in which this node's move was legal, see `SearchConfig::ucb_availability`
+    pub fn availability_count(&self) -> usize {
+        self.availability_count
+    }
+
+    /// Total reward, to be divided by `visit_count` for the mean
+    pub fn reward(&self) -> f64 {
+        self.reward
+    }
+
+    /// Iterations which selected this node and haven't backpropagated yet, only counted with
+    /// `SearchConfig::virtual_loss`
+    pub fn in_flight(&self) -> usize {
+        self.in_flight
+    }
+
+    /// Simulations in which this node's move was played later on by the same player, only
+    /// counted with `SearchConfig::rave`
+    pub fn amaf_visits(&self) -> usize {
+        self.amaf_visits
+    }
+
+    /// Mean reward of the `amaf_visits` simulations, 0 if there were none
+    pub fn amaf_mean(&self) -> f64 {
+        if self.amaf_visits == 0 {
+            0.0
+        } else {
+            self.amaf_reward / self.amaf_visits as f64
+        }
+    }
+
+    pub fn mean(&self) -> f64 {
+        self.value
+            .unwrap_or_else(|| self.reward / self.visit_count as f64)
+    }
+
+    /// Fraction of visits ending in a draw, 0 if unvisited
+    pub fn draw_rate(&self) -> f64 {
+        if self.visit_count == 0 {
+            return 0.0;
+        }
+        self.draws / self.visit_count as f64
+    }
+
+    /// Population variance of the rewards seen by this node
+    pub fn variance(&self) -> f64 {
+        if self.visit_count == 0 {
+            return 0.0;
+        }
+        let n = self.visit_count as f64;
+        let mean = self.reward / n;
+        (self.reward_sq / n - mean * mean).max(0.0)
+    }
+
+    pub fn ucb1(&self, exploration: f64) -> f64 {
+        self.ucb1_with_availability(self.availability_count, exploration)
+    }
+
+    /// Infinite for an unvisited node, so that it's tried before any visited sibling
+    pub fn ucb1_with_availability(&self, availability_count: usize, exploration: f64) -> f64 {
+        if self.visit_count == 0 {
+            return f64::INFINITY;
+        }
+        self.mean()
+            + exploration
+                * ((availability_count.max(1) as f64).ln() / self.visit_count as f64).sqrt()
+    }
+
+    /// UCB1-Tuned, which bounds the exploration term by the observed variance of the rewards
+    pub fn ucb1_tuned(&self) -> f64 {
+        self.ucb1_tuned_with_virtual_loss(self.availability_count, 0.0)
+    }
+
+    /// Mean reward and visit count as if each in-flight iteration had already lost `virtual_loss` simulations
+    fn with_virtual_loss(&self, virtual_loss: f64) -> (f64, f64) {
+        let lost = virtual_loss * self.in_flight as f64;
+        let visits = self.visit_count as f64 + lost;
+        if lost == 0.0 {
+            (self.mean(), visits)
+        } else {
+            (
+                (self.mean() * self.visit_count as f64 - lost) / visits,
+                visits,
+            )
+        }
+    }
+
+    /// UCB1-Tuned as if each in-flight iteration had already lost `virtual_loss` simulations.
+    /// Infinite for an unvisited node, like `ucb1_with_availability`.
+    fn ucb1_tuned_with_virtual_loss(&self, availability_count: usize, virtual_loss: f64) -> f64 {
+        if self.visit_count == 0 {
+            return f64::INFINITY;
+        }
+        let (mean, visits) = self.with_virtual_loss(virtual_loss);
+        let log_availability = (availability_count.max(1) as f64).ln();
+        let variance_bound = self.variance() + (2.0 * log_availability / visits).sqrt();
+        mean + (log_availability / visits * variance_bound.min(0.25)).sqrt()
+    }
+
+    /// PUCT score as if each in-flight iteration had already lost `virtual_loss` simulations.
+    /// Infinite for an unvisited node, like `ucb1_with_availability`.
+    fn puct_with_virtual_loss(
+        &self,
+        availability_count: usize,
+        virtual_loss: f64,
+        exploration: f64,
+        prior: f64,
+    ) -> f64 {
+        if self.visit_count == 0 {
+            return f64::INFINITY;
+        }
+        let (mean, visits) = self.with_virtual_loss(virtual_loss);
+        mean + exploration * prior * (availability_count as f64).sqrt() / (1.0 + visits)
+    }
+
+    /// Mix the all-moves-as-first mean into the mean of a selection `score`, trusting it less as
+    /// visits grow, while keeping the score's exploration term. An unvisited node is scored by
+    /// its all-moves-as-first mean alone.
+    fn rave_score(&self, score: f64, equivalence: f64) -> f64 {
+        if self.amaf_visits == 0 {
+            return score;
+        }
+        let amaf_mean = self.amaf_mean();
+        if self.visit_count == 0 {
+            // `score` may be infinite, which the weight of 0 wouldn't cancel
+            return amaf_mean;
+        }
+        let beta = (equivalence / (3.0 * self.visit_count as f64 + equivalence)).sqrt();
+        score + beta * (amaf_mean - self.mean())
+    }
+
+    /// UCB1 as if each in-flight iteration had already lost `virtual_loss` simulations
+    fn ucb1_with_virtual_loss(
+        &self,
+        availability_count: usize,
+        virtual_loss: f64,
+        exploration: f64,
+    ) -> f64 {
+        if self.visit_count == 0 || self.in_flight == 0 || virtual_loss == 0.0 {
+            return self.ucb1_with_availability(availability_count, exploration);
+        }
+        let (mean, visits) = self.with_virtual_loss(virtual_loss);
+        mean + exploration * ((availability_count.max(1) as f64).ln() / visits).sqrt()
     }
 }
 
-impl<G: Game> Node<G> {
-    fn untried_moves(&self, legal_moves: &[G::Move]) -> Vec<G::Move> {
-        let children = self.children.read().unwrap();
-        legal_moves
-            .iter()
-            .filter(|mov: &&G::Move| !children.iter().any(|c| c.mov.as_ref().unwrap() == *mov))
-            .cloned()
-            .collect::<Vec<_>>()
+/// `f64` which can be updated without locking, stored as its bits
+#[derive(Debug, Default)]
+struct AtomicF64(AtomicU64);
+
+impl AtomicF64 {
+    fn new(value: f64) -> Self {
+        AtomicF64(AtomicU64::new(value.to_bits()))
+    }
+
+    fn load(&self) -> f64 {
+        f64::from_bits(self.0.load(Ordering::Relaxed))
+    }
+
+    fn store(&self, value: f64) {
+        self.0.store(value.to_bits(), Ordering::Relaxed);
+    }
+
+    /// Replace the value with `f(value)`, retrying if another thread changed it meanwhile
+    fn update<F: Fn(f64) -> f64>(&self, f: F) {
+        let _ = self
+            .0
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |bits| {
+                Some(f(f64::from_bits(bits)).to_bits())
+            });
+    }
+
+    fn fetch_add(&self, value: f64) {
+        self.update(|current| current + value);
+    }
+}
+
+/// How an iteration reaches the tree
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Access {
+    /// Other threads may search or read the tree at the same time
+    Shared,
+    /// Only this thread can reach the tree, so `TreeLock`s aren't locked and statistics are
+    /// updated without atomic read-modify-writes.
+    /// Only used by `IsmctsHandler::run_iterations_single_thread` once it has checked this.
+    Exclusive,
+}
+
+impl Access {
+    /// Add `n` to `count`
+    fn add(self, count: &AtomicUsize, n: usize, order: Ordering) {
+        match self {
+            Access::Shared => {
+                count.fetch_add(n, order);
+            }
+            Access::Exclusive => count.store(count.load(Ordering::Relaxed) + n, order),
+        }
+    }
+
+    /// Add `value` to `total`
+    fn add_f64(self, total: &AtomicF64, value: f64) {
+        match self {
+            Access::Shared => total.fetch_add(value),
+            Access::Exclusive => total.store(total.load() + value),
+        }
+    }
+}
+
+/// `RwLock` whose locking is skipped when the tree is searched with `Access::Exclusive`.
+/// Holding a guard of one access while taking a conflicting guard of the other on the same
+/// thread isn't detected, so the iteration code never overlaps guards of the same node.
+#[derive(Default)]
+struct TreeLock<T> {
+    lock: RwLock<()>,
+    data: std::cell::UnsafeCell<T>,
+}
+
+// Safety: `data` is only reached through guards, which hold `lock` unless no other thread can
+// reach the tree
+unsafe impl<T: Send> Send for TreeLock<T> {}
+unsafe impl<T: Send + Sync> Sync for TreeLock<T> {}
+
+struct TreeReadGuard<'a, T> {
+    _lock: Option<std::sync::RwLockReadGuard<'a, ()>>,
+    data: &'a T,
+}
+
+struct TreeWriteGuard<'a, T> {
+    _lock: Option<std::sync::RwLockWriteGuard<'a, ()>>,
+    data: &'a mut T,
+}
+
+impl<T> std::ops::Deref for TreeReadGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.data
+    }
+}
+
+impl<T> std::ops::Deref for TreeWriteGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.data
+    }
+}
+
+impl<T> std::ops::DerefMut for TreeWriteGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.data
+    }
+}
+
+impl<T> TreeLock<T> {
+    fn new(data: T) -> Self {
+        TreeLock {
+            lock: RwLock::new(()),
+            data: std::cell::UnsafeCell::new(data),
+        }
+    }
+
+    fn read(&self) -> LockResult<TreeReadGuard<'_, T>> {
+        self.read_with(Access::Shared)
+    }
+
+    fn write(&self) -> LockResult<TreeWriteGuard<'_, T>> {
+        self.write_with(Access::Shared)
+    }
+
+    fn read_with(&self, access: Access) -> LockResult<TreeReadGuard<'_, T>> {
+        let (lock, poisoned) = match access {
+            Access::Shared => match self.lock.read() {
+                Ok(lock) => (Some(lock), false),
+                Err(poisoned) => (Some(poisoned.into_inner()), true),
+            },
+            Access::Exclusive => (None, false),
+        };
+        // Safety: the lock is held, or no other thread can reach the tree
+        let guard = TreeReadGuard {
+            _lock: lock,
+            data: unsafe { &*self.data.get() },
+        };
+        if poisoned {
+            Err(PoisonError::new(guard))
+        } else {
+            Ok(guard)
+        }
+    }
+
+    fn write_with(&self, access: Access) -> LockResult<TreeWriteGuard<'_, T>> {
+        let (lock, poisoned) = match access {
+            Access::Shared => match self.lock.write() {
+                Ok(lock) => (Some(lock), false),
+                Err(poisoned) => (Some(poisoned.into_inner()), true),
+            },
+            Access::Exclusive => (None, false),
+        };
+        // Safety: the lock is held, or no other thread can reach the tree
+        let guard = TreeWriteGuard {
+            _lock: lock,
+            data: unsafe { &mut *self.data.get() },
+        };
+        if poisoned {
+            Err(PoisonError::new(guard))
+        } else {
+            Ok(guard)
+        }
+    }
+}
+
+/// `NodeStatistics` of a node in the tree, updated by searching threads without locking apart
+/// from the optional per-player rewards and learning rate updates. A non-additive `value` is
+/// stored as NaN when absent.
+#[derive(Debug)]
+struct AtomicStatistics {
+    visit_count: AtomicUsize,
+    availability_count: AtomicUsize,
+    reward: AtomicF64,
+    reward_sq: AtomicF64,
+    value: AtomicF64,
+    in_flight: AtomicUsize,
+    leaf_visits: AtomicUsize,
+    amaf_visits: AtomicUsize,
+    amaf_reward: AtomicF64,
+    draws: AtomicF64,
+    /// Locked, so only allocated once `SearchConfig::players` records a reward
+    player_rewards: OnceLock<Mutex<Vec<f64>>>,
+    /// Held by `SearchConfig::learning_rate` updates, which rescale the rewards by the visits
+    learning: Mutex<()>,
+}
+
+impl Default for AtomicStatistics {
+    fn default() -> Self {
+        NodeStatistics::default().into()
+    }
+}
+
+impl From<NodeStatistics> for AtomicStatistics {
+    fn from(statistics: NodeStatistics) -> Self {
+        AtomicStatistics {
+            visit_count: AtomicUsize::new(statistics.visit_count),
+            availability_count: AtomicUsize::new(statistics.availability_count),
+            reward: AtomicF64::new(statistics.reward),
+            reward_sq: AtomicF64::new(statistics.reward_sq),
+            value: AtomicF64::new(statistics.value.unwrap_or(f64::NAN)),
+            in_flight: AtomicUsize::new(statistics.in_flight),
+            leaf_visits: AtomicUsize::new(statistics.leaf_visits),
+            amaf_visits: AtomicUsize::new(statistics.amaf_visits),
+            amaf_reward: AtomicF64::new(statistics.amaf_reward),
+            draws: AtomicF64::new(statistics.draws),
+            player_rewards: if statistics.player_rewards.is_empty() {
+                OnceLock::new()
+            } else {
+                OnceLock::from(Mutex::new(statistics.player_rewards))
+            },
+            learning: Mutex::new(()),
+        }
+    }
+}
+
+/// Add `rewards` to `totals` player by player, lengthening `totals` if needed
+fn add_player_rewards(totals: &mut Vec<f64>, rewards: &[f64]) {
+    if totals.len() < rewards.len() {
+        totals.resize(rewards.len(), 0.0);
+    }
+    for (total, reward) in totals.iter_mut().zip(rewards) {
+        *total += reward;
+    }
+}
+
+impl AtomicStatistics {
+    /// Current statistics. Fields are read one at a time, so updates by other threads may be
+    /// partially included.
+    fn snapshot(&self) -> NodeStatistics {
+        NodeStatistics {
+            player_rewards: self
+                .player_rewards
+                .get()
+                .map(|rewards| rewards.lock().unwrap().clone())
+                .unwrap_or_default(),
+            ..self.selection_snapshot()
+        }
+    }
+
+    /// Like `snapshot`, but without the `player_rewards` which selection doesn't use, so that
+    /// it never takes a lock
+    fn selection_snapshot(&self) -> NodeStatistics {
+        // Read first, so that the rewards of every visit counted here are included
+        let visit_count = self.visit_count.load(Ordering::Acquire);
+        let value = self.value.load();
+        NodeStatistics {
+            visit_count,
+            availability_count: self.availability_count.load(Ordering::Relaxed),
+            reward: self.reward.load(),
+            reward_sq: self.reward_sq.load(),
+            value: if value.is_nan() { None } else { Some(value) },
+            in_flight: self.in_flight.load(Ordering::Relaxed),
+            leaf_visits: self.leaf_visits.load(Ordering::Relaxed),
+            amaf_visits: self.amaf_visits.load(Ordering::Relaxed),
+            amaf_reward: self.amaf_reward.load(),
+            draws: self.draws.load(),
+            player_rewards: Vec::new(),
+        }
+    }
+
+    /// Add `rewards` to the summed rewards of each player
+    fn add_player_rewards(&self, rewards: &[f64]) {
+        add_player_rewards(
+            &mut self
+                .player_rewards
+                .get_or_init(Default::default)
+                .lock()
+                .unwrap(),
+            rewards,
+        );
+    }
+
+    /// Overwrite every field with `statistics`
+    #[cfg(test)]
+    fn store(&self, statistics: &NodeStatistics) {
+        self.visit_count
+            .store(statistics.visit_count, Ordering::Relaxed);
+        self.availability_count
+            .store(statistics.availability_count, Ordering::Relaxed);
+        self.reward.store(statistics.reward);
+        self.reward_sq.store(statistics.reward_sq);
+        self.value.store(statistics.value.unwrap_or(f64::NAN));
+        self.in_flight
+            .store(statistics.in_flight, Ordering::Relaxed);
+        self.leaf_visits
+            .store(statistics.leaf_visits, Ordering::Relaxed);
+        self.amaf_visits
+            .store(statistics.amaf_visits, Ordering::Relaxed);
+        self.amaf_reward.store(statistics.amaf_reward);
+        self.draws.store(statistics.draws);
+        self.player_rewards
+            .get_or_init(Default::default)
+            .lock()
+            .unwrap()
+            .clone_from(&statistics.player_rewards);
+    }
+
+    /// Add another tree's counts for the same node. A non-additive `value` is kept if present.
+    fn merge(&self, other: &NodeStatistics) {
+        self.visit_count
+            .fetch_add(other.visit_count, Ordering::Relaxed);
+        self.availability_count
+            .fetch_add(other.availability_count, Ordering::Relaxed);
+        self.leaf_visits
+            .fetch_add(other.leaf_visits, Ordering::Relaxed);
+        self.reward.fetch_add(other.reward);
+        self.reward_sq.fetch_add(other.reward_sq);
+        if let Some(value) = other.value {
+            self.value
+                .update(|current| if current.is_nan() { value } else { current });
+        }
+        self.amaf_visits
+            .fetch_add(other.amaf_visits, Ordering::Relaxed);
+        self.amaf_reward.fetch_add(other.amaf_reward);
+        self.draws.fetch_add(other.draws);
+        if !other.player_rewards.is_empty() {
+            self.add_player_rewards(&other.player_rewards);
+        }
+    }
+}
+
+impl<G: Game> Node<G> {
+    fn new_root() -> Arc<Node<G>> {
+        Arc::new(Node {
+            mov: None,
+            parent: Default::default(),
+            children: Default::default(),
+            player_just_moved: None,
+            prior: AtomicF64::new(1.0),
+            terminal: Default::default(),
+            statistics: Default::default(),
+            pruned: Default::default(),
+        })
+    }
+
+    fn untried_moves(&self, legal_moves: &[G::Move], access: Access) -> Vec<G::Move> {
+        let children = self.children.read_with(access).unwrap();
+        legal_moves
+            .iter()
+            .filter(|mov: &&G::Move| !children.iter().any(|c| c.mov.as_ref().unwrap() == *mov))
+            .cloned()
+            .collect::<Vec<_>>()
+    }
+
+    /// Count an opportunity to be selected for each child whose move is in `legal_moves`
+    fn add_availability(&self, legal_moves: &[G::Move], access: Access) {
+        for c in self.children.read_with(access).unwrap().iter() {
+            if legal_moves.contains(c.mov.as_ref().unwrap()) {
+                access.add(&c.statistics.availability_count, 1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    fn find_child(&self, mov: &G::Move, access: Access) -> Option<Arc<Node<G>>> {
+        let children = self.children.read_with(access).unwrap();
+        children
+            .iter()
+            .find(|c| c.mov.as_ref() == Some(mov))
+            .cloned()
+    }
+
+    /// Choose the legal child with the highest `SelectionPolicy` score, or `None` if `unexplored_score` beats them all.
+    /// Ties go to the move listed first by `legal_moves`, or with `tie_salt`, to the first move
+    /// of an order shuffled by it.
+    fn select_child(
+        &self,
+        legal_moves: &[G::Move],
+        run: &SearchRun<G>,
+        unexplored_score: Option<f64>,
+        tie_salt: Option<u64>,
+    ) -> Option<Arc<Node<G>>> {
+        let config = run.config;
+        let parent_visits = self.statistics.selection_snapshot().visit_count;
+        let children = self.children.read_with(run.access).unwrap();
+        let legal_children: Vec<_> = children
+            .iter()
+            .filter(|c| legal_moves.contains(c.mov.as_ref().unwrap()))
+            .collect(); // Need to enumerate twice
+        let root_noise = run
+            .root_noise
+            .filter(|_| self.parent.read_with(run.access).unwrap().is_none());
+
+        let (choice, score, _) = legal_children
+            .iter()
+            .map(|&c| {
+                let statistics = c.statistics.selection_snapshot();
+                let availability = config.ucb_availability.count(&statistics, parent_visits);
+                let virtual_loss = config
+                    .virtual_loss
+                    .magnitude(statistics.visit_count, run.n_threads);
+                let prior = c.prior.load();
+                let prior =
+                    root_noise.map_or(prior, |noise| noise.mix(c.mov.as_ref().unwrap(), prior));
+                let score = config.selection_score(&statistics, availability, virtual_loss, prior);
+                let order = move_order(legal_moves, c.mov.as_ref().unwrap());
+                let tie = match tie_salt {
+                    Some(salt) => {
+                        use std::hash::{Hash, Hasher};
+                        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+                        (salt, order.0).hash(&mut hasher);
+                        hasher.finish()
+                    }
+                    None => order.0 as u64,
+                };
+                (Some(c), OrderedFloat::from(score), std::cmp::Reverse(tie))
+            })
+            .max_by_key(|&(_, score, tie)| (score, tie))
+            .unwrap_or((
+                None,
+                OrderedFloat::from(f64::NEG_INFINITY),
+                std::cmp::Reverse(0),
+            ));
+        // To avoid backprop needing to recalculate/store which nodes were available, update
+        // availablity count now, also when an untried move is expanded instead
+        legal_children.iter().for_each(|c| {
+            run.access
+                .add(&c.statistics.availability_count, 1, Ordering::Relaxed);
+        });
+        if unexplored_score.is_some_and(|s| OrderedFloat::from(s) > score) {
+            return None;
+        }
+        if let Some(chosen) = choice.filter(|_| config.virtual_loss != VirtualLoss::Disabled) {
+            run.access
+                .add(&chosen.statistics.in_flight, 1, Ordering::Relaxed);
+        }
+        choice.cloned()
+    }
+
+    /// UCB1 score for an untried move under `SearchConfig::pessimistic_expansion`: the worst
+    /// visited legal sibling's mean, explored as if it had been tried once
+    fn pessimistic_unexplored_score(
+        &self,
+        legal_moves: &[G::Move],
+        exploration: f64,
+        access: Access,
+    ) -> Option<f64> {
+        let parent_visits = self.statistics.selection_snapshot().visit_count;
+        let worst_mean = self
+            .children
+            .read_with(access)
+            .unwrap()
+            .iter()
+            .filter(|c| legal_moves.contains(c.mov.as_ref().unwrap()))
+            .map(|c| c.statistics.selection_snapshot())
+            .filter(|s| s.visit_count > 0)
+            .map(|s| OrderedFloat::from(s.mean()))
+            .min()?;
+        let unexplored = NodeStatistics {
+            visit_count: 1,
+            reward: worst_mean.into_inner(),
+            ..Default::default()
+        };
+        Some(unexplored.ucb1_with_availability(parent_visits, exploration))
+    }
+
+    fn add_child(
+        self: Arc<Self>,
+        mov: G::Move,
+        player_tag: G::PlayerTag,
+        prior: f64,
+        statistics: SharedStatistics,
+        node_count: &AtomicUsize,
+    ) -> Arc<Node<G>> {
+        self.add_children(
+            vec![(mov, prior, statistics)],
+            player_tag,
+            node_count,
+            Access::Shared,
+        )
+        .pop()
+        .unwrap()
+    }
+
+    /// Add a child for each of `new_children`'s moves with its prior and statistics, returning
+    /// the children in the same order
+    fn add_children(
+        self: Arc<Self>,
+        new_children: Vec<(G::Move, f64, SharedStatistics)>,
+        player_tag: G::PlayerTag,
+        node_count: &AtomicUsize,
+        access: Access,
+    ) -> Vec<Arc<Node<G>>> {
+        // Obtain a write lock on children to ensure that no other thread can add a child at the same time
+        let mut children = self.children.write_with(access).unwrap();
+
+        new_children
+            .into_iter()
+            .map(|(mov, prior, statistics)| {
+                // Check if the child with the same move already exists (race condition prevention)
+                if let Some(existing_child) = children.iter().find(|c| c.mov.as_ref() == Some(&mov))
+                {
+                    return Arc::clone(existing_child);
+                }
+
+                // We update the availabilty count during selection instead of backprop,
+                // but the visit count _is_ updated during backprop, so the availability
+                // of the new node needs a +1 because expansion happens after selection.
+                access.add(&statistics.availability_count, 1, Ordering::Relaxed);
+
+                let child = Arc::new(Node {
+                    mov: Some(mov),
+                    parent: TreeLock::new(Some(Arc::downgrade(&self))),
+                    children: Default::default(),
+                    player_just_moved: Some(player_tag),
+                    prior: AtomicF64::new(prior),
+                    terminal: Default::default(),
+                    statistics,
+                    pruned: AtomicBool::new(self.pruned.load(Ordering::Relaxed)),
+                });
+
+                children.push(Arc::clone(&child));
+                if !self.pruned.load(Ordering::Relaxed) {
+                    access.add(node_count, 1, Ordering::Relaxed);
+                }
+                child
+            })
+            .collect()
+    }
+
+    /// Add the statistics of every node in `other`'s tree to the matching node of this tree,
+    /// creating any nodes this tree lacks
+    fn merge_from(self: &Arc<Self>, other: &Arc<Node<G>>) {
+        let mut stack = vec![(Arc::clone(self), Arc::clone(other))];
+        while let Some((dest, src)) = stack.pop() {
+            dest.statistics.merge(&src.statistics.snapshot());
+            let mut dest_children = dest.children.write().unwrap();
+            for src_child in src.children.read().unwrap().iter() {
+                let dest_child = match dest_children.iter().find(|c| c.mov == src_child.mov) {
+                    Some(c) => Arc::clone(c),
+                    None => {
+                        let c = Arc::new(Node {
+                            mov: src_child.mov.clone(),
+                            parent: TreeLock::new(Some(Arc::downgrade(&dest))),
+                            children: Default::default(),
+                            player_just_moved: src_child.player_just_moved,
+                            prior: AtomicF64::new(src_child.prior.load()),
+                            terminal: AtomicBool::new(src_child.terminal.load(Ordering::Relaxed)),
+                            statistics: Default::default(),
+                            pruned: Default::default(),
+                        });
+                        dest_children.push(Arc::clone(&c));
+                        c
+                    }
+                };
+                stack.push((dest_child, Arc::clone(src_child)));
+            }
+        }
+    }
+
+    /// Record one visit, with the mean result over `leaves` (the end states of the simulations).
+    /// `distance` counts the moves from this node to the node the simulation started from.
+    fn update(&self, leaves: &[G], config: &SearchConfig<G>, distance: usize, access: Access) {
+        let discount = config.discount.powi(distance as i32);
+        let result = self
+            .player_just_moved
+            .map(|p| discount * config.mean_reward(leaves, p));
+
+        let value = match (&config.backprop, self.player_just_moved, result) {
+            (BackpropAggregation::Average, _, _) | (_, None, _) | (_, _, None) => None,
+            (aggregation, Some(player), Some(r)) => {
+                let children: Vec<_> = self
+                    .children
+                    .read_with(access)
+                    .unwrap()
+                    .iter()
+                    .filter_map(|c| {
+                        let statistics = c.statistics.selection_snapshot();
+                        (statistics.visit_count > 0)
+                            .then(|| (c.player_just_moved.unwrap(), statistics.mean()))
+                    })
+                    .collect();
+                aggregation.aggregate(player, r, &children)
+            }
+        };
+
+        let statistics = &self.statistics;
+        // Rewards are added before the visit is counted with release ordering, and
+        // `AtomicStatistics::snapshot` reads the visit count first with acquire ordering, so that
+        // concurrent selection never sees a visit without its reward
+        let mut visit_counted = false;
+        if let (Some(r), Some(player)) = (result, self.player_just_moved) {
+            // Over several leaves this differs from `r * r`, the square of the mean
+            let r_sq = discount * discount * config.mean_squared_reward(leaves, player);
+            match config.learning_rate {
+                Some(lr) => {
+                    // The visits read here must still be current when the rewards are replaced
+                    // and the visit is counted, so concurrent updates take turns
+                    let _learning = statistics.learning.lock().unwrap();
+                    let previous_visits = statistics.visit_count.load(Ordering::Relaxed);
+                    if previous_visits > 0 {
+                        // Keep `reward / visit_count` equal to an exponential moving average
+                        let n = (previous_visits + 1) as f64;
+                        let previous_visits = previous_visits as f64;
+                        statistics
+                            .reward
+                            .update(|reward| n * (reward / previous_visits * (1.0 - lr) + r * lr));
+                        statistics.reward_sq.update(|reward_sq| {
+                            n * (reward_sq / previous_visits * (1.0 - lr) + r_sq * lr)
+                        });
+                    } else {
+                        access.add_f64(&statistics.reward, r);
+                        access.add_f64(&statistics.reward_sq, r_sq);
+                    }
+                    access.add(&statistics.visit_count, 1, Ordering::Release);
+                    visit_counted = true;
+                }
+                None => {
+                    access.add_f64(&statistics.reward, r);
+                    access.add_f64(&statistics.reward_sq, r_sq);
+                }
+            }
+        }
+        if let Some(value) = value {
+            statistics.value.store(value);
+        }
+        if let Some(players) = &config.players {
+            let rewards: Vec<_> = players
+                .iter()
+                .map(|&p| discount * config.mean_reward(leaves, p))
+                .collect();
+            statistics.add_player_rewards(&rewards);
+        }
+        if let Some(p) = self.player_just_moved {
+            let draws = leaves
+                .iter()
+                .filter(|leaf| leaf.outcome(p) == Some(Outcome::Draw))
+                .count();
+            if draws > 0 {
+                access.add_f64(&statistics.draws, draws as f64 / leaves.len() as f64);
+            }
+        }
+        if distance == 0 {
+            access.add(&statistics.leaf_visits, 1, Ordering::Relaxed);
+        }
+        if !visit_counted {
+            access.add(&statistics.visit_count, 1, Ordering::Release);
+        }
+    }
+
+    /// Credit the children whose move was played by the same player in `played_after`, the moves
+    /// made after this node in a simulation ending in `leaves`
+    fn update_amaf(
+        &self,
+        played_after: &[(G::PlayerTag, G::Move)],
+        leaves: &[G],
+        config: &SearchConfig<G>,
+        access: Access,
+    ) {
+        for child in self.children.read_with(access).unwrap().iter() {
+            let player = child.player_just_moved.unwrap();
+            let mov = child.mov.as_ref().unwrap();
+            if played_after.iter().any(|(p, m)| *p == player && m == mov) {
+                let result = config.mean_reward(leaves, player);
+                access.add_f64(&child.statistics.amaf_reward, result);
+                access.add(&child.statistics.amaf_visits, 1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// The nodes `depth` moves below this one
+    fn descendants_at(self: &Arc<Self>, depth: usize, access: Access) -> Vec<Arc<Node<G>>> {
+        let mut level = vec![Arc::clone(self)];
+        for _ in 0..depth {
+            level = level
+                .iter()
+                .flat_map(|node| node.children.read_with(access).unwrap().clone())
+                .collect();
+        }
+        level
+    }
+
+    /// Visit this node and all of its descendants along with their depth below this node
+    fn for_each_descendant<F: FnMut(&Arc<Node<G>>, usize)>(self: &Arc<Self>, mut f: F) {
+        let mut stack = vec![(Arc::clone(self), 0)];
+        while let Some((node, depth)) = stack.pop() {
+            f(&node, depth);
+            stack.extend(
+                node.children
+                    .read()
+                    .unwrap()
+                    .iter()
+                    .map(|c| (Arc::clone(c), depth + 1)),
+            );
+        }
+    }
+
+    /// Copy this node and everything beneath it into a new tree rooted at the copy
+    fn deep_copy(&self) -> Arc<Node<G>> {
+        let copy_node = |src: &Node<G>, parent: Option<Weak<Node<G>>>| {
+            Arc::new(Node {
+                mov: src.mov.clone(),
+                parent: TreeLock::new(parent),
+                children: Default::default(),
+                player_just_moved: src.player_just_moved,
+                prior: AtomicF64::new(src.prior.load()),
+                terminal: AtomicBool::new(src.terminal.load(Ordering::Relaxed)),
+                statistics: Arc::new(src.statistics.snapshot().into()),
+                pruned: Default::default(),
+            })
+        };
+
+        let root = copy_node(self, None);
+        let mut stack = vec![(
+            self.children
+                .read()
+                .unwrap()
+                .iter()
+                .cloned()
+                .collect::<Vec<_>>(),
+            Arc::clone(&root),
+        )];
+        while let Some((src_children, dst)) = stack.pop() {
+            for src in src_children {
+                let child = copy_node(&src, Some(Arc::downgrade(&dst)));
+                dst.children.write().unwrap().push(Arc::clone(&child));
+                stack.push((
+                    src.children.read().unwrap().iter().cloned().collect(),
+                    child,
+                ));
+            }
+        }
+        root
+    }
+}
+
+/// Why `IsmctsHandler::make_move` failed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MoveError {
+    /// The move isn't available in the root state, which is left unchanged
+    Illegal,
+    /// The move was applied, but search never reached the resulting position,
+    /// so searching continues from a fresh tree
+    Unexplored,
+}
+
+impl std::fmt::Display for MoveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            MoveError::Illegal => write!(f, "move is not legal"),
+            MoveError::Unexplored => write!(f, "move was not explored by search"),
+        }
+    }
+}
+
+impl std::error::Error for MoveError {}
+
+/// Why `IsmctsHandler::best_move_checked` has no move
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NoMoveReason {
+    /// The root state has no available moves
+    Terminal,
+    /// There are available moves, but none has been searched yet
+    Unexplored,
+}
+
+impl std::fmt::Display for NoMoveReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            NoMoveReason::Terminal => write!(f, "no moves are available"),
+            NoMoveReason::Unexplored => write!(f, "no moves have been searched"),
+        }
+    }
+}
+
+impl std::error::Error for NoMoveReason {}
+
+/// State of a running search, see `IsmctsHandler::run_timed_with_progress`
+#[derive(Clone, Debug)]
+pub struct SearchProgress<M> {
+    pub elapsed: Duration,
+    /// Visits of the root's children
+    pub total_visits: usize,
+    pub best_move_so_far: Option<M>,
+}
+
+/// Search statistics of a single root move
+#[derive(Clone, Debug, PartialEq)]
+pub struct MoveStat<M> {
+    pub mov: M,
+    pub visits: usize,
+    /// `None` if the move has not been visited
+    pub mean_reward: Option<f64>,
+}
+
+impl<M> MoveStat<M> {
+    fn new(mov: M, statistics: &NodeStatistics) -> Self {
+        MoveStat {
+            mov,
+            visits: statistics.visit_count,
+            mean_reward: if statistics.visit_count > 0 {
+                Some(statistics.mean())
+            } else {
+                None
+            },
+        }
+    }
+}
+
+/// Training sample of the search's choice at one position, see
+/// `IsmctsHandler::record_policy_target`
+#[derive(Clone, Debug, PartialEq)]
+pub struct PolicyTarget<M, P> {
+    /// `Game::state_hash` of the position
+    pub state_key: Option<u64>,
+    /// Player to move at the position
+    pub player: P,
+    /// Share of the root's visits of each legal move, summing to 1
+    pub visit_distribution: Vec<(M, f64)>,
+    /// Result for `player`, once attached with `attach_result`
+    pub result: Option<f64>,
+}
+
+impl<M, P: Copy> PolicyTarget<M, P> {
+    /// Record the result for `player` of the game ending in `final_state`, as `config` of the
+    /// search that recorded this target derives it, see `SearchConfig::result`
+    pub fn attach_result<G: Game<Move = M, PlayerTag = P>>(
+        &mut self,
+        final_state: &G,
+        config: &SearchConfig<G>,
+    ) {
+        self.result = config.result(final_state, self.player);
+    }
+}
+
+/// Best root move at one moment of a search, see `IsmctsHandler::best_move_snapshot`
+#[derive(Clone, Debug, PartialEq)]
+pub struct BestMoveSnapshot<M> {
+    pub mov: M,
+    pub visits: usize,
+    /// Visits of all root moves, read together with `visits`
+    pub total_visits: usize,
+}
+
+/// Evaluation of an expanded root move, see `IsmctsHandler::ranked_moves` and
+/// `IsmctsHandler::children_summary`
+#[derive(Clone, Debug, PartialEq)]
+pub struct RankedMove<M> {
+    pub mov: M,
+    pub visits: usize,
+    /// `None` if the move has not been visited
+    pub mean_reward: Option<f64>,
+    /// Score selection would give the move, by `SearchConfig::selection`, `rave` and
+    /// `ucb_availability`, without virtual loss. With the default `Selection::Ucb1` and no RAVE,
+    /// this is infinite if the move has not been visited.
+    pub ucb1: f64,
+    /// Iterations in which the move was legal
+    pub availability: usize,
+}
+
+/// Overview of a finished search, see `IsmctsHandler::run_iterations_with_stats`
+#[derive(Clone, Debug)]
+pub struct SearchSummary<M> {
+    /// Iterations performed by this search
+    pub total_iterations: usize,
+    pub root_children: Vec<MoveStat<M>>,
+    pub max_child_visits: usize,
+    pub elapsed: Duration,
+    /// Most visited root move, as in `IsmctsHandler::best_move`
+    pub best_move: Option<M>,
+}
+
+/// Search statistics of a node in the tree
+#[derive(Clone, Debug)]
+pub struct ChildInfo<G: Game> {
+    /// Move which entered this node, `None` for the root
+    pub mov: Option<G::Move>,
+    pub player_just_moved: Option<G::PlayerTag>,
+    /// Distance from the root
+    pub depth: usize,
+    pub visit_count: usize,
+    pub availability_count: usize,
+    pub reward: f64,
+    /// Whether the game was found to be over at this node
+    pub terminal: bool,
+    /// Summed rewards of each of `SearchConfig::players`, empty if unset
+    pub player_rewards: Vec<f64>,
+}
+
+impl<G: Game> ChildInfo<G> {
+    fn new(node: &Node<G>, depth: usize) -> Self {
+        let statistics = node.statistics.snapshot();
+        ChildInfo {
+            mov: node.mov.clone(),
+            player_just_moved: node.player_just_moved,
+            depth,
+            visit_count: statistics.visit_count,
+            availability_count: statistics.availability_count,
+            reward: statistics.reward,
+            terminal: node.terminal.load(Ordering::Relaxed),
+            player_rewards: statistics.player_rewards,
+        }
+    }
+
+    pub fn mean_reward(&self) -> Option<f64> {
+        if self.visit_count == 0 {
+            None
+        } else {
+            Some(self.reward / self.visit_count as f64)
+        }
+    }
+}
+
+/// Breadth first traversal which only locks a node when it is reached
+struct NodeIter<G: Game> {
+    queue: std::collections::VecDeque<(Arc<Node<G>>, usize)>,
+}
+
+impl<G: Game> Iterator for NodeIter<G> {
+    type Item = ChildInfo<G>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (node, depth) = self.queue.pop_front()?;
+        self.queue.extend(
+            node.children
+                .read()
+                .unwrap()
+                .iter()
+                .map(|c| (Arc::clone(c), depth + 1)),
+        );
+        Some(ChildInfo::new(&node, depth))
+    }
+}
+
+/// Engine's view of one step of a replayed game
+#[derive(Clone, Debug)]
+pub struct StepReport<M> {
+    pub logged_move: M,
+    pub engine_move: Option<M>,
+    /// Mean reward of `engine_move` for the player making it
+    pub engine_value: Option<f64>,
+    pub agreed: bool,
+}
+
+/// A node captured by `TreeSnapshot`
+#[derive(Clone, Debug)]
+pub struct SnapshotNode<M> {
+    /// Index of the parent within `TreeSnapshot::nodes`
+    pub parent: Option<usize>,
+    pub depth: usize,
+    pub mov: Option<M>,
+    pub visit_count: usize,
+    pub availability_count: usize,
+    pub reward: f64,
+    /// UCB1 score with the handler's exploration constant, `None` for the root and unvisited nodes
+    pub ucb1: Option<f64>,
+}
+
+/// Copy of the search tree's statistics, in breadth first order starting at the root
+#[derive(Clone, Debug)]
+pub struct TreeSnapshot<M> {
+    pub nodes: Vec<SnapshotNode<M>>,
+}
+
+impl<M: std::fmt::Debug> TreeSnapshot<M> {
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph ismcts {\n");
+        for (i, node) in self.nodes.iter().enumerate() {
+            let mov = node
+                .mov
+                .as_ref()
+                .map_or_else(|| "root".to_string(), |m| format!("{:?}", m));
+            let ucb1 = node
+                .ucb1
+                .map_or_else(String::new, |u| format!("\\nucb1: {:.4}", u));
+            dot.push_str(&format!(
+                "    n{} [label=\"{}\\nvisits: {}\\navailability: {}\\nreward: {}{}\"];\n",
+                i,
+                escape_string(&mov),
+                node.visit_count,
+                node.availability_count,
+                node.reward,
+                ucb1
+            ));
+            if let Some(p) = node.parent {
+                dot.push_str(&format!("    n{} -> n{};\n", p, i));
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    pub fn to_json(&self) -> String {
+        let nodes: Vec<_> = self
+            .nodes
+            .iter()
+            .map(|node| {
+                format!(
+                    "{{\"parent\":{},\"depth\":{},\"move\":{},\"visit_count\":{},\"availability_count\":{},\"reward\":{}}}",
+                    node.parent.map_or_else(|| "null".to_string(), |p| p.to_string()),
+                    node.depth,
+                    node.mov
+                        .as_ref()
+                        .map_or_else(|| "null".to_string(), |m| format!("\"{}\"", escape_string(&format!("{:?}", m)))),
+                    node.visit_count,
+                    node.availability_count,
+                    node.reward
+                )
+            })
+            .collect();
+        format!("{{\"nodes\":[{}]}}", nodes.join(","))
+    }
+}
+
+/// Escape a string for use inside double quotes in DOT or JSON output
+fn escape_string(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+pub struct IsmctsHandler<G: Game> {
+    root_state: G,
+    root_node: Arc<Node<G>>,
+    config: SearchConfig<G>,
+    // Search state is shared with the threads of `start_pondering`
+    /// Iterations completed since construction or the last `reset`
+    lifetime_iterations: Arc<AtomicUsize>,
+    result_counts: Arc<Mutex<BTreeMap<OrderedFloat<f64>, usize>>>,
+    transpositions: Arc<Mutex<HashMap<u64, SharedStatistics>>>,
+    /// Nodes in the tree, including the root
+    node_count: Arc<AtomicUsize>,
+    /// Held by the thread pruning the tree for `SearchConfig::max_nodes`
+    pruning: Arc<Mutex<()>>,
+    root_noise: Option<RootNoise<G::Move>>,
+}
+
+/// Search running on background threads, started by `IsmctsHandler::start_pondering`.
+/// Dropping the handle stops the search too. The handle borrows the handler, so it can't be
+/// changed while the threads search its tree.
+#[cfg(all(feature = "threads", not(target_arch = "wasm32")))]
+pub struct PonderHandle<'a> {
+    stop: Arc<AtomicBool>,
+    threads: Vec<std::thread::JoinHandle<()>>,
+    handler: std::marker::PhantomData<&'a ()>,
+}
+
+#[cfg(all(feature = "threads", not(target_arch = "wasm32")))]
+impl PonderHandle<'_> {
+    /// Signal the threads to stop after their current iteration, and wait for them to finish
+    pub fn stop(self) {
+        drop(self);
+    }
+}
+
+#[cfg(all(feature = "threads", not(target_arch = "wasm32")))]
+impl Drop for PonderHandle<'_> {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        for thread in self.threads.drain(..) {
+            // A panicking search thread has already reported its panic, and panicking again
+            // while dropping could abort
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Dirichlet noise mixed into the priors of the root's children, see `IsmctsHandler::add_root_noise`
+#[derive(Clone)]
+struct RootNoise<M> {
+    epsilon: f64,
+    /// Each root move's noise, scaled to the sum of the unmixed priors
+    noise: Vec<(M, f64)>,
+}
+
+impl<M: PartialEq> RootNoise<M> {
+    fn mix(&self, mov: &M, prior: f64) -> f64 {
+        let noise = self
+            .noise
+            .iter()
+            .find(|(m, _)| m == mov)
+            .map_or(0.0, |(_, n)| *n);
+        (1.0 - self.epsilon) * prior + self.epsilon * noise
+    }
+}
+
+/// Chainable construction of an `IsmctsHandler` with a custom `SearchConfig`
+#[derive(Clone, Debug)]
+pub struct IsmctsBuilder<G: Game> {
+    config: SearchConfig<G>,
+}
+
+impl<G: Game> Default for IsmctsBuilder<G> {
+    fn default() -> Self {
+        IsmctsBuilder {
+            config: Default::default(),
+        }
+    }
+}
+
+impl<G: Game> IsmctsBuilder<G> {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Start from `config` instead of the defaults
+    pub fn config(mut self, config: SearchConfig<G>) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn exploration(mut self, exploration: f64) -> Self {
+        self.config.exploration = exploration;
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.config.seed = Some(seed);
+        self
+    }
+
+    pub fn selection(mut self, selection: Selection) -> Self {
+        self.config.selection = selection;
+        self
+    }
+
+    /// Roll out with `policy` instead of `Game::rollout_policy`
+    pub fn rollout<F>(mut self, policy: F) -> Self
+    where
+        F: Fn(&G, &mut StdRng) -> Option<G::Move> + Send + Sync + 'static,
+    {
+        self.config.rollout = RolloutPolicy::Custom(Arc::new(policy));
+        self
+    }
+
+    /// Rollouts follow `SearchConfig::rollout`; when it has no move, this many sampled moves are
+    /// ranked by `Game::move_priority`
+    pub fn rollout_move_sample_size(mut self, sample_size: usize) -> Self {
+        self.config.rollout_move_sample_size = Some(sample_size);
+        self
+    }
+
+    pub fn zero_sum(mut self, perspective: G::PlayerTag) -> Self {
+        self.config.zero_sum = Some(perspective);
+        self
+    }
+
+    pub fn max_rollout_moves(mut self, max_moves: usize) -> Self {
+        self.config.max_rollout_moves = max_moves;
+        self
+    }
+
+    /// Scale rewards from `low..=high` into 0 to 1, panicking unless `low < high`
+    pub fn reward_bounds(mut self, low: f64, high: f64) -> Self {
+        assert!(
+            low < high,
+            "reward_bounds must have low < high, got ({}, {})",
+            low,
+            high
+        );
+        self.config.reward_bounds = Some((low, high));
+        self
+    }
+
+    pub fn virtual_loss(mut self, virtual_loss: VirtualLoss) -> Self {
+        self.config.virtual_loss = virtual_loss;
+        self
+    }
+
+    pub fn progressive_widening(mut self, widening: ProgressiveWidening) -> Self {
+        self.config.progressive_widening = Some(widening);
+        self
+    }
+
+    pub fn rave(mut self, equivalence: f64) -> Self {
+        self.config.rave = Some(equivalence);
+        self
+    }
+
+    /// Select with `Selection::Custom(policy)`
+    pub fn selection_policy<P: SelectionPolicy + 'static>(mut self, policy: P) -> Self {
+        self.config.selection = Selection::Custom(Arc::new(policy));
+        self
+    }
+
+    pub fn fpu(mut self, value: f64) -> Self {
+        self.config.fpu = Some(value);
+        self
+    }
+
+    pub fn transposition_table(mut self, enabled: bool) -> Self {
+        self.config.transposition_table = enabled;
+        self
+    }
+
+    pub fn build(self, root_state: G) -> IsmctsHandler<G> {
+        IsmctsHandler {
+            root_state,
+            root_node: Node::new_root(),
+            config: self.config,
+            lifetime_iterations: Default::default(),
+            result_counts: Default::default(),
+            transpositions: Default::default(),
+            node_count: Arc::new(AtomicUsize::new(1)),
+            pruning: Default::default(),
+            root_noise: None,
+        }
+    }
+}
+
+impl<G: Game> IsmctsHandler<G> {
+    /// Handler with the default `SearchConfig`, see `IsmctsBuilder` for other configurations
+    pub fn new(root_state: G) -> Self {
+        IsmctsBuilder::new().build(root_state)
+    }
+
+    pub fn builder() -> IsmctsBuilder<G> {
+        IsmctsBuilder::new()
+    }
+
+    /// Like `new`, but with UCB1 exploration constant `exploration` instead of `sqrt(2)`
+    pub fn with_exploration(root_state: G, exploration: f64) -> Self {
+        IsmctsBuilder::new()
+            .exploration(exploration)
+            .build(root_state)
+    }
+
+    /// Like `new`, but selecting with PUCT weighted by `c_puct`
+    pub fn new_puct(root_state: G, c_puct: f64) -> Self {
+        IsmctsBuilder::new()
+            .selection(Selection::Puct)
+            .exploration(c_puct)
+            .build(root_state)
+    }
+
+    /// Like `new`, for a two-player zero-sum game whose `result` is only defined from
+    /// `perspective`'s point of view, see `SearchConfig::zero_sum`
+    pub fn new_zero_sum(root_state: G, perspective: G::PlayerTag) -> Self {
+        IsmctsBuilder::new().zero_sum(perspective).build(root_state)
+    }
+
+    /// Like `new`, but searches are reproducible for a fixed number of threads and iterations
+    pub fn with_seed(root_state: G, seed: u64) -> Self {
+        IsmctsBuilder::new().seed(seed).build(root_state)
+    }
+
+    /// Start a new game from `root_state`, discarding the tree and the lifetime iteration count
+    pub fn reset(&mut self, root_state: G) {
+        self.root_state = root_state;
+        self.root_node = Node::new_root();
+        self.lifetime_iterations.store(0, Ordering::Relaxed);
+        self.result_counts.lock().unwrap().clear();
+        self.transpositions.lock().unwrap().clear();
+        self.node_count.store(1, Ordering::Relaxed);
+        self.root_noise = None;
+    }
+
+    /// Discard the tree and the lifetime iteration count, searching the same position afresh
+    pub fn clear_tree(&mut self) {
+        self.reset(self.root_state.clone());
+    }
+
+    /// Iterations completed since construction or the last `reset`
+    pub fn lifetime_iterations(&self) -> usize {
+        self.lifetime_iterations.load(Ordering::Relaxed)
+    }
+
+    /// Terminal results from the perspective of the player searching, with the fraction of
+    /// simulations ending in each. Empty unless `SearchConfig::record_result_distribution` is set.
+    pub fn result_distribution(&self) -> Vec<(f64, f64)> {
+        let counts = self.result_counts.lock().unwrap();
+        let total: usize = counts.values().sum();
+        counts
+            .iter()
+            .map(|(result, count)| (result.into_inner(), *count as f64 / total as f64))
+            .collect()
+    }
+
+    /// Iterations left of a `total` budget for the whole game
+    pub fn remaining_budget(&self, total: usize) -> usize {
+        total.saturating_sub(self.lifetime_iterations())
+    }
+
+    /// Search each of `worlds` (determinizations of `state`) with perfect information and
+    /// report the best move found in each. Worlds with no moves are left out.
+    pub fn per_world_analysis(
+        state: G,
+        worlds: Vec<G>,
+        n_threads: usize,
+        n_iterations_per_thread: usize,
+    ) -> Vec<(G, G::Move)> {
+        worlds
+            .into_iter()
+            .filter_map(|world| {
+                let mut ismcts = IsmctsHandler::new(state.clone());
+                ismcts.config.fixed_determination = Some(world);
+                ismcts.run_iterations(n_threads, n_iterations_per_thread);
+                let best_move = ismcts.best_move()?;
+                Some((ismcts.config.fixed_determination.take().unwrap(), best_move))
+            })
+            .collect()
+    }
+
+    /// Best move for each of `states`. As many states are searched at once as fit in the
+    /// machine's available parallelism with `threads_per_state` threads each.
+    pub fn batch_search(
+        states: Vec<G>,
+        threads_per_state: usize,
+        n_iterations_per_thread: usize,
+    ) -> Vec<Option<G::Move>> {
+        let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+        let concurrent_states = (cores / threads_per_state.max(1)).clamp(1, states.len().max(1));
+        let handlers: Vec<_> = states.into_iter().map(IsmctsHandler::new).collect();
+        let next = AtomicUsize::new(0);
+        spawn_n_threads(concurrent_states, |_| {
+            while let Some(ismcts) = handlers.get(next.fetch_add(1, Ordering::Relaxed)) {
+                ismcts.run_iterations(threads_per_state, n_iterations_per_thread);
+            }
+        });
+        handlers.iter().map(|ismcts| ismcts.best_move()).collect()
+    }
+
+    pub fn config(&self) -> &SearchConfig<G> {
+        &self.config
+    }
+
+    pub fn config_mut(&mut self) -> &mut SearchConfig<G> {
+        &mut self.config
+    }
+
+    /// If the current root position appears (by `Game::state_hash`) in `prior`'s tree,
+    /// start from a copy of the matching subtree's statistics. Returns whether a match was found.
+    pub fn warm_start_from(&mut self, prior: &IsmctsHandler<G>) -> bool {
+        let target = match self.root_state.state_hash() {
+            Some(h) => h,
+            None => return false,
+        };
+        match find_node_by_hash(&prior.root_state, &prior.root_node, target, usize::MAX) {
+            Some(node) => {
+                self.root_node = node.deep_copy();
+                self.recount_nodes();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Continue from `source`'s tree, e.g. one kept by `make_move` while searching on the
+    /// opponent's time. Returns false, leaving this handler unchanged, unless `source` is at the
+    /// same position by `Game::state_hash`, so games without it need `graft_unchecked`.
+    pub fn graft(&mut self, source: IsmctsHandler<G>) -> bool {
+        match (self.root_state.state_hash(), source.root_state.state_hash()) {
+            (Some(hash), Some(source_hash)) if hash == source_hash => {
+                self.graft_unchecked(source);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Like `graft`, but takes `source`'s tree without checking that it was searched from
+    /// this handler's position
+    pub fn graft_unchecked(&mut self, source: IsmctsHandler<G>) {
+        self.root_node = source.root_node;
+        // Backprop stops at the root, wherever `source`'s tree came from
+        *self.root_node.parent.write().unwrap() = None;
+        *self.transpositions.lock().unwrap() =
+            std::mem::take(&mut source.transpositions.lock().unwrap());
+        self.root_noise = None;
+        self.recount_nodes();
+    }
+
+    /// Apply `mov` to the root state. The explored subtree beneath `mov` becomes the new tree,
+    /// so later searches continue from its visit counts.
+    /// An illegal move is rejected, while an unexplored move is still applied with a fresh tree.
+    pub fn make_move(&mut self, mov: &G::Move) -> Result<(), MoveError> {
+        if !self
+            .root_state
+            .available_moves()
+            .into_iter()
+            .any(|m| m == *mov)
+        {
+            return Err(MoveError::Illegal);
+        }
+        if self.apply_move(mov) {
+            Ok(())
+        } else {
+            Err(MoveError::Unexplored)
+        }
+    }
+
+    /// Mix `Dirichlet(alpha)` noise with weight `epsilon` into the priors of the root's moves,
+    /// so that repeated searches of the same position explore differently.
+    /// Priors weight exploration under `Selection::Puct` and `Selection::Ucb1`.
+    /// The noise lasts until the root changes, and calling this again replaces it.
+    pub fn add_root_noise<R: Rng + ?Sized>(&mut self, alpha: f64, epsilon: f64, rng: &mut R) {
+        assert!(
+            alpha > 0.0,
+            "Dirichlet noise needs a positive alpha, not {}",
+            alpha
+        );
+        let gamma = rand_distr::Gamma::new(alpha, 1.0).unwrap();
+        let moves: Vec<_> = self.root_state.available_moves().into_iter().collect();
+        let samples: Vec<f64> = moves.iter().map(|_| gamma.sample(rng)).collect();
+        let total: f64 = samples.iter().sum();
+        // Priors from `Game::move_priors` sum to 1, while every move has a prior of 1 otherwise
+        let prior_sum = if self.config.selection.uses_priors() {
+            1.0
+        } else {
+            moves.len() as f64
+        };
+        self.root_noise = Some(RootNoise {
+            epsilon,
+            noise: moves
+                .into_iter()
+                .zip(samples)
+                .map(|(m, sample)| (m, sample / total * prior_sum))
+                .collect(),
+        });
+    }
+
+    /// Like `make_move`, but without checking that `mov` is legal
+    pub fn make_move_unchecked(&mut self, mov: &G::Move) {
+        self.apply_move(mov);
+    }
+
+    /// Returns whether a node for the new position was found in the tree
+    fn apply_move(&mut self, mov: &G::Move) -> bool {
+        let child_node = self.root_node.find_child(mov, Access::Shared);
+
+        let previous_state = self.root_state.clone();
+        self.root_state.make_move(mov);
+        if let Some(determination) = &mut self.config.fixed_determination {
+            determination.make_move(mov);
+        }
+        let next_root = child_node.or_else(|| {
+            // The move was never explored, but the resulting position may have been reached
+            // deeper in the tree by another move order
+            let target = self.root_state.state_hash()?;
+            find_node_by_hash(
+                &previous_state,
+                &self.root_node,
+                target,
+                self.config.reroot_search_depth,
+            )
+        });
+        let explored = next_root.is_some();
+        self.root_noise = None;
+        self.root_node = next_root.unwrap_or_else(Node::new_root);
+        // Keep the subtree's statistics, and let the rest of the old tree be dropped
+        *self.root_node.parent.write().unwrap() = None;
+        self.recount_nodes();
+        explored
+    }
+
+    /// Whether the invariant of `NodeStatistics::leaf_visits` holds throughout the tree, if the
+    /// configuration promises it. Only meaningful while no search is running.
+    #[cfg(test)]
+    pub(crate) fn visits_are_consistent(&self) -> bool {
+        let config = &self.config;
+        if config.transposition_table || config.backup_depth.is_some() || config.max_nodes.is_some()
+        {
+            return true;
+        }
+        let mut consistent = true;
+        self.root_node.for_each_descendant(|node, _| {
+            let statistics = node.statistics.snapshot();
+            let children_visits: usize = node
+                .children
+                .read()
+                .unwrap()
+                .iter()
+                .map(|c| c.statistics.snapshot().visit_count)
+                .sum();
+            consistent &= statistics.visit_count == statistics.leaf_visits + children_visits;
+        });
+        consistent
+    }
+
+    /// Number of nodes in the tree, including the root
+    pub fn node_count(&self) -> usize {
+        self.node_count.load(Ordering::Relaxed)
+    }
+
+    /// Length of the longest chain of moves below the root
+    pub fn tree_depth(&self) -> usize {
+        let mut max_depth = 0;
+        self.root_node
+            .for_each_descendant(|_, depth| max_depth = max_depth.max(depth));
+        max_depth
+    }
+
+    /// Count the nodes again after the root is replaced, and drop the transpositions left behind
+    fn recount_nodes(&mut self) {
+        let mut count = 0;
+        self.root_node.for_each_descendant(|_, _| count += 1);
+        self.node_count.store(count, Ordering::Relaxed);
+        trim_transpositions(&self.root_node, &self.transpositions);
+    }
+
+    /// Search each position of a recorded game before applying the logged move
+    pub fn replay(
+        &mut self,
+        moves: &[G::Move],
+        n_threads: usize,
+        n_iterations_per_thread: usize,
+    ) -> Vec<StepReport<G::Move>> {
+        let mut reports = Vec::with_capacity(moves.len());
+        for mov in moves {
+            self.run_iterations(n_threads, n_iterations_per_thread);
+            let engine_move = self.best_move();
+            let engine_value = engine_move.as_ref().map(|m| {
+                self.root_node
+                    .find_child(m, Access::Shared)
+                    .unwrap()
+                    .statistics
+                    .snapshot()
+                    .mean()
+            });
+            reports.push(StepReport {
+                logged_move: mov.clone(),
+                agreed: engine_move.as_ref() == Some(mov),
+                engine_move,
+                engine_value,
+            });
+
+            self.make_move_unchecked(mov);
+        }
+        reports
+    }
+
+    fn search_run(&self, n_threads: usize) -> SearchRun<'_, G> {
+        self.config.check_reward_bounds();
+        SearchRun {
+            config: &self.config,
+            n_threads,
+            forced_move: None,
+            completed_iterations: &self.lifetime_iterations,
+            result_counts: &self.result_counts,
+            transpositions: &self.transpositions,
+            // Offset so that consecutive searches don't repeat the same random sequence
+            seed: self
+                .config
+                .seed
+                .map(|seed| seed.wrapping_add(self.lifetime_iterations() as u64)),
+            stop: None,
+            node_count: &self.node_count,
+            pruning: &self.pruning,
+            root_noise: self.root_noise.as_ref(),
+            access: Access::Shared,
+        }
+    }
+
+    /// Like `run_iterations`, also summarizing the search and the root's children
+    pub fn run_iterations_with_stats(
+        &self,
+        n_threads: usize,
+        n_iterations_per_thread: usize,
+    ) -> SearchSummary<G::Move> {
+        let initial_iterations = self.lifetime_iterations();
+        let start = Instant::now();
+        self.run_iterations(n_threads, n_iterations_per_thread);
+        let elapsed = start.elapsed();
+
+        let root_children: Vec<_> = self
+            .root_children_statistics()
+            .into_iter()
+            .map(|(mov, s)| MoveStat::new(mov, &s))
+            .collect();
+        let available_moves: Vec<_> = self.root_state.available_moves().into_iter().collect();
+        let best = root_children
+            .iter()
+            .max_by_key(|m| (m.visits, move_order(&available_moves, &m.mov)));
+        SearchSummary {
+            total_iterations: self.lifetime_iterations() - initial_iterations,
+            max_child_visits: best.map_or(0, |m| m.visits),
+            best_move: best.map(|m| m.mov.clone()),
+            root_children,
+            elapsed,
+        }
+    }
+
+    /// The tree's locks are taken even with one thread, since the tree may be read from other
+    /// threads during the search, such as by `best_move_snapshot` or `start_pondering`.
+    /// `run_iterations_single_thread` doesn't take them.
+    pub fn run_iterations(&self, n_threads: usize, n_iterations_per_thread: usize) {
+        let run = self.search_run(n_threads);
+        spawn_n_threads(n_threads, |thread_index| {
+            ismcts_work_thread_iterations(
+                self.root_state.clone(),
+                Arc::clone(&self.root_node),
+                &run,
+                thread_index,
+                n_iterations_per_thread,
+            )
+        });
+    }
+
+    /// Same search as `run_iterations(1, n_iterations)`, but faster, since borrowing the handler
+    /// mutably shows that no other thread can read the tree, so it is searched without locking.
+    /// If the threads of a forgotten `PonderHandle` are still searching, the tree is locked as
+    /// usual.
+    pub fn run_iterations_single_thread(&mut self, n_iterations: usize) {
+        let run = SearchRun {
+            access: self.exclusive_access(),
+            ..self.search_run(1)
+        };
+        ismcts_work_thread_iterations(
+            self.root_state.clone(),
+            Arc::clone(&self.root_node),
+            &run,
+            0,
+            n_iterations,
+        );
+    }
+
+    /// `Access::Exclusive`, unless another thread still holds the root. The threads of
+    /// `start_pondering` are the only ones to outlive the method starting them, and they keep
+    /// the root until they finish. `nodes` borrows the handler, so no node is held elsewhere.
+    fn exclusive_access(&mut self) -> Access {
+        if Arc::strong_count(&self.root_node) == 1 {
+            Access::Exclusive
+        } else {
+            Access::Shared
+        }
+    }
+
+    /// Like `run_iterations`, but never takes the game's lifetime iterations past `total_game_budget`.
+    /// Returns the number of iterations each thread ran. At least one thread is used.
+    pub fn run_within_global_budget(
+        &self,
+        n_threads: usize,
+        n_iterations_per_thread: usize,
+        total_game_budget: usize,
+    ) -> usize {
+        let n_threads = n_threads.max(1);
+        let n_iterations =
+            n_iterations_per_thread.min(self.remaining_budget(total_game_budget) / n_threads);
+        if n_iterations > 0 {
+            self.run_iterations(n_threads, n_iterations);
+        }
+        n_iterations
+    }
+
+    /// Like `run_iterations`, but every iteration starts with `mov` to concentrate search on it
+    pub fn run_focused(&self, mov: &G::Move, n_threads: usize, n_iterations_per_thread: usize) {
+        assert!(
+            self.root_state
+                .available_moves()
+                .into_iter()
+                .any(|m| m == *mov),
+            "Move must be legal"
+        );
+        let run = SearchRun {
+            forced_move: Some(mov),
+            ..self.search_run(n_threads)
+        };
+        spawn_n_threads(n_threads, |thread_index| {
+            ismcts_work_thread_iterations(
+                self.root_state.clone(),
+                Arc::clone(&self.root_node),
+                &run,
+                thread_index,
+                n_iterations_per_thread,
+            )
+        });
+    }
+
+    /// Like `run_iterations`, but each thread searches its own tree without any lock contention.
+    /// The trees are then summed into this handler's tree. Returns the best move by `merge`.
+    pub fn run_iterations_root_parallel(
+        &self,
+        n_threads: usize,
+        n_iterations_per_thread: usize,
+        merge: MergeStrategy,
+    ) -> Option<G::Move> {
+        let run = self.search_run(1);
+        let trees = Mutex::new(Vec::with_capacity(n_threads));
+        spawn_n_threads(n_threads, |thread_index| {
+            let tree = self.search_own_tree(&run, thread_index, n_iterations_per_thread);
+            trees.lock().unwrap().push((thread_index, tree));
+        });
+        self.merge_trees(trees.into_inner().unwrap(), merge)
+    }
+
+    /// Like `run_iterations_root_parallel`, but `n_iterations` in total are split into
+    /// `n_work_items` separate trees, which the threads take in turn. Each tree's random numbers
+    /// depend only on its index, so seeded searches give the same result for any `n_threads`.
+    pub fn run_iterations_partitioned(
+        &self,
+        n_threads: usize,
+        n_iterations: usize,
+        n_work_items: usize,
+    ) {
+        let run = self.search_run(1);
+        let next_item = AtomicUsize::new(0);
+        let trees = Mutex::new(Vec::with_capacity(n_work_items));
+        spawn_n_threads(n_threads, |_| loop {
+            let item = next_item.fetch_add(1, Ordering::Relaxed);
+            if item >= n_work_items {
+                break;
+            }
+            let item_iterations =
+                n_iterations / n_work_items + usize::from(item < n_iterations % n_work_items);
+            let tree = self.search_own_tree(&run, item, item_iterations);
+            trees.lock().unwrap().push((item, tree));
+        });
+        self.merge_trees(trees.into_inner().unwrap(), MergeStrategy::SumStatistics);
+    }
+
+    /// Search a new tree, separate from this handler's, with the random numbers of `thread_index`
+    fn search_own_tree(
+        &self,
+        run: &SearchRun<G>,
+        thread_index: usize,
+        n_iterations: usize,
+    ) -> Arc<Node<G>> {
+        let root = Node::new_root();
+        // Transpositions and node limits only apply within each thread's own tree
+        let transpositions = Default::default();
+        let node_count = AtomicUsize::new(1);
+        let pruning = Default::default();
+        let run = SearchRun {
+            transpositions: &transpositions,
+            node_count: &node_count,
+            pruning: &pruning,
+            ..*run
+        };
+        ismcts_work_thread_iterations(
+            self.root_state.clone(),
+            Arc::clone(&root),
+            &run,
+            thread_index,
+            n_iterations,
+        );
+        root
+    }
+
+    /// Sum indexed trees from `search_own_tree` into this handler's tree, returning the best move
+    /// by `strategy`
+    fn merge_trees(
+        &self,
+        mut trees: Vec<(usize, Arc<Node<G>>)>,
+        strategy: MergeStrategy,
+    ) -> Option<G::Move> {
+        // Merge in a fixed order so that seeded searches are reproducible
+        trees.sort_by_key(|(index, _)| *index);
+        let voted = match strategy {
+            MergeStrategy::SumStatistics => None,
+            MergeStrategy::MajorityVote => Some(self.voted_move(&trees, false)),
+            MergeStrategy::WeightedByVisits => Some(self.voted_move(&trees, true)),
+        };
+        for (_, tree) in trees {
+            self.root_node.merge_from(&tree);
+        }
+        let mut count = 0;
+        self.root_node.for_each_descendant(|_, _| count += 1);
+        self.node_count.store(count, Ordering::Relaxed);
+        voted.unwrap_or_else(|| self.best_move())
+    }
+
+    /// Move with the most votes from `trees`, each voting for its most visited root move, with
+    /// the share of the tree's visits the move received if `weighted`. Ties, within a tree or
+    /// between votes, go to the move listed first by `available_moves`.
+    fn voted_move(&self, trees: &[(usize, Arc<Node<G>>)], weighted: bool) -> Option<G::Move> {
+        let available_moves: Vec<_> = self.root_state.available_moves().into_iter().collect();
+        let mut votes: Vec<(G::Move, f64)> = Vec::new();
+        for (_, tree) in trees {
+            let children = tree.children.read().unwrap();
+            let visits = |c: &Arc<Node<G>>| c.statistics.visit_count.load(Ordering::Relaxed);
+            let Some(best) = children.iter().max_by_key(|c| {
+                (
+                    visits(c),
+                    move_order(&available_moves, c.mov.as_ref().unwrap()),
+                )
+            }) else {
+                continue;
+            };
+            let total: usize = children.iter().map(visits).sum();
+            let weight = if weighted {
+                visits(best) as f64 / total.max(1) as f64
+            } else {
+                1.0
+            };
+            let mov = best.mov.as_ref().unwrap();
+            match votes.iter_mut().find(|(m, _)| m == mov) {
+                Some((_, votes)) => *votes += weight,
+                None => votes.push((mov.clone(), weight)),
+            }
+        }
+        votes
+            .into_iter()
+            .max_by_key(|(mov, votes)| {
+                (
+                    OrderedFloat::from(*votes),
+                    move_order(&available_moves, mov),
+                )
+            })
+            .map(|(mov, _)| mov)
+    }
+
+    pub fn run_timed(&self, n_threads: usize, time: Duration) {
+        self.run_timed_cancellable(n_threads, time, Default::default());
+    }
+
+    /// Keep searching on `n_threads` background threads, e.g. on the opponent's time, until the
+    /// returned handle is stopped. The handler may be read in the meantime, but can only be
+    /// changed, such as by `make_move`, after pondering stops.
+    #[cfg(all(feature = "threads", not(target_arch = "wasm32")))]
+    pub fn start_pondering(&self, n_threads: usize) -> PonderHandle<'_>
+    where
+        G: 'static,
+    {
+        let stop = Arc::new(AtomicBool::new(false));
+        let seed = self.search_run(n_threads).seed;
+        let threads = (0..n_threads)
+            .map(|thread_index| {
+                let root_state = self.root_state.clone();
+                let root_node = Arc::clone(&self.root_node);
+                let config = self.config.clone();
+                let completed_iterations = Arc::clone(&self.lifetime_iterations);
+                let result_counts = Arc::clone(&self.result_counts);
+                let transpositions = Arc::clone(&self.transpositions);
+                let node_count = Arc::clone(&self.node_count);
+                let pruning = Arc::clone(&self.pruning);
+                let root_noise = self.root_noise.clone();
+                let stop = Arc::clone(&stop);
+                std::thread::spawn(move || {
+                    let run = SearchRun {
+                        config: &config,
+                        n_threads,
+                        forced_move: None,
+                        completed_iterations: &completed_iterations,
+                        result_counts: &result_counts,
+                        transpositions: &transpositions,
+                        seed,
+                        stop: Some(&stop),
+                        node_count: &node_count,
+                        pruning: &pruning,
+                        root_noise: root_noise.as_ref(),
+                        access: Access::Shared,
+                    };
+                    ismcts_work_thread_timed(
+                        root_state,
+                        root_node,
+                        &run,
+                        thread_index,
+                        Duration::MAX,
+                    );
+                })
+            })
+            .collect();
+        PonderHandle {
+            stop,
+            threads,
+            handler: std::marker::PhantomData,
+        }
+    }
+
+    /// Search for this move's share of `remaining` clock time over `moves_left` moves, scaled by
+    /// `branching_hint`, the number of moves available in the position, see `managed_time`.
+    /// Returns the time actually used.
+    pub fn run_managed(
+        &self,
+        n_threads: usize,
+        remaining: Duration,
+        moves_left: usize,
+        branching_hint: usize,
+    ) -> Duration {
+        let start = Instant::now();
+        self.run_timed(
+            n_threads,
+            managed_time(remaining, moves_left, branching_hint),
+        );
+        start.elapsed()
+    }
+
+    /// Like `run_timed`, but returns early once `cancel` is set, keeping the tree searched so far
+    pub fn run_timed_cancellable(&self, n_threads: usize, time: Duration, cancel: Arc<AtomicBool>) {
+        // Without threads, each thread's share would run for the whole time in turn
+        let n_threads = if cfg!(all(feature = "threads", not(target_arch = "wasm32"))) {
+            n_threads
+        } else {
+            1
+        };
+        let run = SearchRun {
+            stop: Some(&cancel),
+            ..self.search_run(n_threads)
+        };
+        spawn_n_threads(n_threads, |thread_index| {
+            ismcts_work_thread_timed(
+                self.root_state.clone(),
+                Arc::clone(&self.root_node),
+                &run,
+                thread_index,
+                time,
+            )
+        });
+    }
+
+    /// Like `run_timed`, but stops once the most visited root move leads the runner-up by at least
+    /// `min_gap` visits and by more than the iterations the remaining time is expected to allow.
+    /// Returns the number of iterations performed.
+    pub fn run_until_confident(
+        &self,
+        n_threads: usize,
+        max_time: Duration,
+        min_gap: usize,
+    ) -> usize {
+        let initial_iterations = self.lifetime_iterations();
+        self.run_timed_coordinated(
+            n_threads,
+            max_time,
+            self.config.confidence_check_interval,
+            |elapsed| {
+                let visits: Vec<usize> = {
+                    let children = self.root_node.children.read().unwrap();
+                    children
+                        .iter()
+                        .map(|c| c.statistics.snapshot().visit_count)
+                        .collect()
+                };
+                let iterations = self.lifetime_iterations() - initial_iterations;
+                best_move_decided(visits, iterations, elapsed, max_time, min_gap)
+            },
+        );
+        self.lifetime_iterations() - initial_iterations
+    }
+
+    /// Search in batches of `batch` iterations per thread until the root's visit distribution
+    /// moves less than `epsilon` (as the L1 distance between successive batches) for `window`
+    /// batches in a row, or until the batch which reaches `max_iterations` in total.
+    /// Gives up after a batch which runs no iterations, as when `batch` or `n_threads` is 0.
+    /// Returns the number of iterations performed.
+    pub fn run_until_stable(
+        &self,
+        n_threads: usize,
+        batch: usize,
+        window: usize,
+        epsilon: f64,
+        max_iterations: usize,
+    ) -> usize {
+        let initial_iterations = self.lifetime_iterations();
+        let mut previous = self.visit_distribution();
+        let mut stable_batches = 0;
+        while stable_batches < window
+            && self.lifetime_iterations() - initial_iterations < max_iterations
+        {
+            let before = self.lifetime_iterations();
+            self.run_iterations(n_threads, batch);
+            if self.lifetime_iterations() == before {
+                break;
+            }
+            let current = self.visit_distribution();
+            let distance: f64 = previous
+                .iter()
+                .zip(&current)
+                .map(|((_, before), (_, after))| (before - after).abs())
+                .sum();
+            if distance < epsilon {
+                stable_batches += 1;
+            } else {
+                stable_batches = 0;
+            }
+            previous = current;
+        }
+        self.lifetime_iterations() - initial_iterations
+    }
+
+    /// Like `run_timed`, calling `callback` with the search's progress every `every`
+    /// and once more when the search is over
+    pub fn run_timed_with_progress<F>(
+        &self,
+        n_threads: usize,
+        time: Duration,
+        every: Duration,
+        callback: F,
+    ) where
+        F: Fn(&SearchProgress<G::Move>) + Sync,
+    {
+        let start = Instant::now();
+        let progress = |elapsed| SearchProgress {
+            elapsed,
+            total_visits: self.total_visits(),
+            best_move_so_far: self.best_move(),
+        };
+        self.run_timed_coordinated(n_threads, time, every, |elapsed| {
+            callback(&progress(elapsed));
+            false
+        });
+        callback(&progress(start.elapsed()));
+    }
+
+    /// Search for `time` while the calling thread calls `check` with the elapsed time every
+    /// `interval`, ending the search early once it returns true
+    #[cfg(all(feature = "threads", not(target_arch = "wasm32")))]
+    fn run_timed_coordinated<F: FnMut(Duration) -> bool>(
+        &self,
+        n_threads: usize,
+        time: Duration,
+        interval: Duration,
+        mut check: F,
+    ) {
+        let stop = AtomicBool::new(false);
+        let run = SearchRun {
+            stop: Some(&stop),
+            ..self.search_run(n_threads)
+        };
+        let start = Instant::now();
+        thread::scope(|s| {
+            for thread_index in 0..n_threads {
+                let run = &run;
+                s.spawn(move |_| {
+                    ismcts_work_thread_timed(
+                        self.root_state.clone(),
+                        Arc::clone(&self.root_node),
+                        run,
+                        thread_index,
+                        time,
+                    )
+                });
+            }
+
+            loop {
+                std::thread::sleep(interval.min(time.saturating_sub(start.elapsed())));
+                let elapsed = start.elapsed();
+                if elapsed >= time {
+                    break;
+                }
+                if check(elapsed) {
+                    stop.store(true, Ordering::Relaxed);
+                    break;
+                }
+            }
+        })
+        .unwrap();
+    }
+
+    /// Without threads, search on the calling thread alone, calling `check` between iterations
+    /// once `interval` has passed since the last call
+    #[cfg(not(all(feature = "threads", not(target_arch = "wasm32"))))]
+    fn run_timed_coordinated<F: FnMut(Duration) -> bool>(
+        &self,
+        _n_threads: usize,
+        time: Duration,
+        interval: Duration,
+        mut check: F,
+    ) {
+        let run = self.search_run(1);
+        let mut pool = StatePool::new(self.config.reuse_state_buffers);
+        let mut rng = run.rng(0);
+        let start = Instant::now();
+        let mut next_check = interval;
+        while start.elapsed() < time {
+            ismcts_work_iteration(&self.root_state, &self.root_node, &run, &mut pool, &mut rng);
+            let elapsed = start.elapsed();
+            if elapsed >= time {
+                break;
+            }
+            if elapsed >= next_check {
+                next_check = elapsed + interval;
+                if check(elapsed) {
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Expected line of play, following the most visited legal child from the root for up to
+    /// `max_len` moves. Legality is judged in a single determinization from the root player's
+    /// point of view, so with hidden information the line is only one possibility and may
+    /// differ between calls.
+    pub fn principal_variation(&self, max_len: usize) -> Vec<G::Move> {
+        let mut state = self.root_state.clone();
+        match &self.config.fixed_determination {
+            Some(determination) => state.reset_to(determination),
+            None => {
+                let observer = state.current_player();
+                state.randomize_determination(observer, &mut self.search_run(1).rng(0));
+            }
+        }
+
+        let mut line = Vec::new();
+        let mut node = Arc::clone(&self.root_node);
+        while line.len() < max_len {
+            let available_moves: Vec<_> = state.available_moves().into_iter().collect();
+            let next = node
+                .children
+                .read()
+                .unwrap()
+                .iter()
+                .filter(|c| available_moves.contains(c.mov.as_ref().unwrap()))
+                .max_by_key(|c| {
+                    (
+                        c.statistics.visit_count.load(Ordering::Relaxed),
+                        move_order(&available_moves, c.mov.as_ref().unwrap()),
+                    )
+                })
+                .cloned();
+            match next {
+                Some(child) => node = child,
+                None => break,
+            }
+            let mov = node.mov.clone().unwrap();
+            state.make_move(&mov);
+            line.push(mov);
+        }
+        line
+    }
+
+    pub fn best_move(&self) -> Option<G::Move> {
+        self.best_move_checked().ok()
+    }
+
+    /// Most visited root move, or why there is none
+    pub fn best_move_checked(&self) -> Result<G::Move, NoMoveReason> {
+        let available_moves: Vec<_> = self.root_state.available_moves().into_iter().collect();
+        let children = self.root_node.children.read().unwrap();
+        // Ties go to the move listed first by `available_moves`
+        match children.iter().max_by_key(|c| {
+            (
+                c.statistics.snapshot().visit_count,
+                move_order(&available_moves, c.mov.as_ref().unwrap()),
+            )
+        }) {
+            Some(c) => Ok(c.mov.clone().unwrap()),
+            None if self.root_state.num_available_moves() == 0 => Err(NoMoveReason::Terminal),
+            None => Err(NoMoveReason::Unexplored),
+        }
+    }
+
+    /// Most visited root move, safe to call from another thread while a search is running.
+    /// Visit counts are collected under a single read lock of the root's children, so the move
+    /// and totals come from the same view of the tree, which the search keeps growing meanwhile.
+    pub fn best_move_snapshot(&self) -> Option<BestMoveSnapshot<G::Move>> {
+        let visits: Vec<_> = {
+            let children = self.root_node.children.read().unwrap();
+            children
+                .iter()
+                .map(|c| {
+                    (
+                        c.mov.clone().unwrap(),
+                        c.statistics.visit_count.load(Ordering::Relaxed),
+                    )
+                })
+                .collect()
+        };
+
+        let available_moves: Vec<_> = self.root_state.available_moves().into_iter().collect();
+        let total_visits = visits.iter().map(|(_, v)| v).sum();
+        visits
+            .into_iter()
+            .max_by_key(|(mov, visits)| (*visits, move_order(&available_moves, mov)))
+            .map(|(mov, visits)| BestMoveSnapshot {
+                mov,
+                visits,
+                total_visits,
+            })
+    }
+
+    /// Like `best_move`, but `None` if it has fewer than `min` visits, meaning the search should
+    /// be continued, for example with `ensure_visits`
+    pub fn best_move_min_visits(&self, min: usize) -> Option<G::Move> {
+        self.best_move().filter(|_| self.max_visits() >= min)
+    }
+
+    /// Search until the most visited root move has at least `min` visits, returning whether it
+    /// does. Returns immediately if the root state has no moves, and gives up once a round of
+    /// iterations adds no visits to the root's children, as under `SearchConfig::backup_depth`.
+    pub fn ensure_visits(&self, n_threads: usize, min: usize) -> bool {
+        if self.root_state.num_available_moves() == 0 {
+            return false;
+        }
+        let n_threads = n_threads.max(1);
+        loop {
+            let visits = self.max_visits();
+            let missing = min.saturating_sub(visits);
+            if missing == 0 {
+                return true;
+            }
+            self.run_iterations(n_threads, missing.div_ceil(n_threads));
+            if self.max_visits() == visits {
+                return false;
+            }
+        }
+    }
+
+    /// Like `best_move`, but a random legal move if nothing has been searched yet
+    pub fn best_move_or_random<R: Rng + ?Sized>(&self, rng: &mut R) -> Option<G::Move> {
+        match self.best_move_checked() {
+            Ok(mov) => Some(mov),
+            Err(NoMoveReason::Terminal) => None,
+            Err(NoMoveReason::Unexplored) => {
+                self.root_state.available_moves().into_iter().choose(rng)
+            }
+        }
+    }
+
+    pub fn best_move_by(&self, criterion: BestMoveCriterion) -> Option<G::Move> {
+        match criterion {
+            BestMoveCriterion::MostVisits => self.best_move(),
+            BestMoveCriterion::HighestReward { min_visits } => {
+                let available_moves: Vec<_> =
+                    self.root_state.available_moves().into_iter().collect();
+                self.root_children_statistics()
+                    .into_iter()
+                    .filter(|(_, s)| s.visit_count > 0 && s.visit_count >= min_visits)
+                    .max_by_key(|(mov, s)| {
+                        (
+                            OrderedFloat::from(s.mean()),
+                            move_order(&available_moves, mov),
+                        )
+                    })
+                    .map(|(mov, _)| mov)
+            }
+            BestMoveCriterion::RobustMax => match self.visit_value_disagreement() {
+                Some(_) => None,
+                None => self.best_move(),
+            },
+        }
     }
 
-    fn select_child(&self, legal_moves: &[G::Move]) -> Option<Arc<Node<G>>> {
-        let children = self.children.read().unwrap();
-        let legal_children: Vec<_> = children
+    /// Pick a root move with probability proportional to `visits^(1 / temperature)`.
+    /// A temperature of 0 is `best_move`, and moves are uniform if none have been visited.
+    pub fn sample_move<R: Rng + ?Sized>(&self, temperature: f64, rng: &mut R) -> Option<G::Move> {
+        if temperature <= 0.0 {
+            return self.best_move();
+        }
+        let statistics = self.root_children_statistics();
+        let max_visits = statistics.iter().map(|(_, s)| s.visit_count).max()?;
+        if max_visits == 0 {
+            return statistics.choose(rng).map(|(mov, _)| mov.clone());
+        }
+        // Relative to the most visited child so that low temperatures don't overflow
+        let weights = statistics
             .iter()
-            .filter(|c| legal_moves.iter().any(|m| c.mov.as_ref().unwrap() == m))
-            .collect(); // Need to enumerate twice
+            .map(|(_, s)| (s.visit_count as f64 / max_visits as f64).powf(1.0 / temperature));
+        let index = rand::distributions::WeightedIndex::new(weights).ok()?;
+        Some(statistics[index.sample(rng)].0.clone())
+    }
 
-        let choice = legal_children
-            .iter()
-            .max_by_key(|c| OrderedFloat::from(c.statistics.read().unwrap().ucb1()))
-            .cloned();
-        // To avoid backprop needing to recalculate/store which nodes were available, update availablity count now
-        legal_children
+    /// The most visited and the highest mean reward root moves, if they differ. Ties go to the
+    /// move listed first by `available_moves`, as in `best_move`.
+    pub fn visit_value_disagreement(&self) -> Option<(G::Move, G::Move)> {
+        let available_moves: Vec<_> = self.root_state.available_moves().into_iter().collect();
+        let visited: Vec<_> = self
+            .root_children_statistics()
+            .into_iter()
+            .filter(|(_, s)| s.visit_count > 0)
+            .collect();
+        let (most_visited, _) = visited
             .iter()
-            .for_each(|c| c.statistics.write().unwrap().availability_count += 1);
-        choice.cloned()
+            .max_by_key(|(mov, s)| (s.visit_count, move_order(&available_moves, mov)))?;
+        let (highest_value, _) = visited.iter().max_by_key(|(mov, s)| {
+            (
+                OrderedFloat::from(s.mean()),
+                move_order(&available_moves, mov),
+            )
+        })?;
+        if most_visited == highest_value {
+            None
+        } else {
+            Some((most_visited.clone(), highest_value.clone()))
+        }
     }
 
-    fn add_child(self: Arc<Self>, mov: G::Move, player_tag: G::PlayerTag) -> Arc<Node<G>> {
-        // Obtain a write lock on children to ensure that no other thread can add a child at the same time
-        let mut children = self.children.write().unwrap();
-
-        // Check if the child with the same move already exists (race condition prevention)
-        if let Some(existing_child) = children.iter().find(|c| c.mov.as_ref() == Some(&mov)) {
-            return Arc::clone(existing_child);
+    /// Copy the statistics of every node up to `max_depth` below the root
+    pub fn tree_snapshot(&self, max_depth: usize) -> TreeSnapshot<G::Move> {
+        let mut nodes = Vec::new();
+        let mut queue = std::collections::VecDeque::new();
+        queue.push_back((Arc::clone(&self.root_node), None, 0));
+        while let Some((node, parent, depth)) = queue.pop_front() {
+            let statistics = node.statistics.snapshot();
+            let index = nodes.len();
+            nodes.push(SnapshotNode {
+                parent,
+                depth,
+                mov: node.mov.clone(),
+                visit_count: statistics.visit_count,
+                availability_count: statistics.availability_count,
+                reward: statistics.reward,
+                ucb1: if parent.is_some() && statistics.visit_count > 0 {
+                    Some(statistics.ucb1(self.config.exploration))
+                } else {
+                    None
+                },
+            });
+            if depth < max_depth {
+                for child in node.children.read().unwrap().iter() {
+                    queue.push_back((Arc::clone(child), Some(index), depth + 1));
+                }
+            }
         }
+        TreeSnapshot { nodes }
+    }
 
-        let p = Arc::downgrade(&self);
-        let child = Arc::new(Node {
-            mov: Some(mov),
-            parent: Some(p),
-            children: Default::default(),
-            player_just_moved: Some(player_tag),
-            statistics: RwLock::new(NodeStatistics {
-                // We update the availabilty count during selection instead of backprop,
-                // but the visit count _is_ updated during backprop, so the availability
-                // of the new node needs a +1 because expansion happens after selection.
-                availability_count: 1,
-                ..Default::default()
-            }),
-        });
+    /// Graphviz DOT graph of the tree down to `max_depth`
+    pub fn to_dot(&self, max_depth: usize) -> String {
+        self.tree_snapshot(max_depth).to_dot()
+    }
 
-        children.push(Arc::clone(&child));
-        child
+    /// Pass a snapshot of the tree to `sink` every `interval` until `stop` is set,
+    /// then once more. Meant to be run alongside a search on another thread.
+    /// No locks are held while `sink` runs.
+    pub fn stream_tree_snapshots<F>(
+        &self,
+        interval: Duration,
+        max_depth: usize,
+        stop: &AtomicBool,
+        mut sink: F,
+    ) where
+        F: FnMut(TreeSnapshot<G::Move>),
+    {
+        loop {
+            let stopping = stop.load(Ordering::SeqCst);
+            sink(self.tree_snapshot(max_depth));
+            if stopping {
+                break;
+            }
+            std::thread::sleep(interval);
+        }
     }
 
-    fn update(&self, terminal_state: &G) {
-        let mut statistics = self.statistics.write().unwrap();
+    /// Probability that the most visited root move truly has a higher mean reward than the
+    /// runner-up, using a normal approximation of the difference between their means. The means
+    /// are `NodeStatistics::mean`, so the value of a non-additive `BackpropAggregation`.
+    pub fn best_move_confidence_probability(&self) -> Option<f64> {
+        let statistics = self.root_children_statistics_most_visited_first();
+        let (best, runner_up) = match statistics.as_slice() {
+            [(_, best), (_, runner_up), ..] if runner_up.visit_count > 0 => (best, runner_up),
+            _ => return None,
+        };
 
-        statistics.visit_count += 1;
-        if let Some(p) = &self.player_just_moved {
-            statistics.reward += terminal_state.result(*p).unwrap_or_default();
+        let mean_difference = best.mean() - runner_up.mean();
+        let standard_error = (best.variance() / best.visit_count as f64
+            + runner_up.variance() / runner_up.visit_count as f64)
+            .sqrt();
+        if standard_error == 0.0 {
+            return Some(match mean_difference.partial_cmp(&0.0) {
+                Some(std::cmp::Ordering::Greater) => 1.0,
+                Some(std::cmp::Ordering::Less) => 0.0,
+                _ => 0.5,
+            });
         }
+        Some(normal_cdf(mean_difference / standard_error))
     }
-}
 
-pub struct IsmctsHandler<G: Game> {
-    root_state: G,
-    root_node: Arc<Node<G>>,
-}
+    /// The two most visited root moves, best first
+    #[allow(clippy::type_complexity)]
+    pub fn top_two(&self) -> Option<(MoveStat<G::Move>, MoveStat<G::Move>)> {
+        let mut top = self
+            .root_children_statistics_most_visited_first()
+            .into_iter()
+            .map(|(mov, s)| MoveStat::new(mov, &s));
+        Some((top.next()?, top.next()?))
+    }
 
-impl<G: Game> IsmctsHandler<G> {
-    pub fn new(root_state: G) -> Self {
-        let root_node = Arc::new(Node {
-            mov: None,
-            parent: None,
-            children: Default::default(),
-            player_just_moved: None,
-            statistics: Default::default(),
+    /// Mean reward of each root child with the half-width of its 95% confidence interval,
+    /// which is infinite for children with fewer than two visits
+    pub fn move_confidence(&self) -> Vec<(G::Move, f64, f64)> {
+        self.root_children_statistics()
+            .into_iter()
+            .map(|(mov, s)| {
+                let n = s.visit_count as f64;
+                match s.visit_count {
+                    0 => (mov, 0.0, f64::INFINITY),
+                    1 => (mov, s.mean(), f64::INFINITY),
+                    _ => {
+                        let sample_variance = s.variance() * n / (n - 1.0);
+                        (mov, s.mean(), 1.96 * (sample_variance / n).sqrt())
+                    }
+                }
+            })
+            .collect()
+    }
+
+    /// Move, visit count and mean reward (0 if unvisited) of each root child, most visited first
+    pub fn move_statistics(&self) -> Vec<(G::Move, usize, f64)> {
+        self.root_children_statistics_most_visited_first()
+            .into_iter()
+            .map(|(mov, s)| {
+                let mean = if s.visit_count > 0 { s.mean() } else { 0.0 };
+                (mov, s.visit_count, mean)
+            })
+            .collect()
+    }
+
+    /// `root_children_statistics` in the order `best_move` ranks the moves
+    fn root_children_statistics_most_visited_first(&self) -> Vec<(G::Move, NodeStatistics)> {
+        let available_moves: Vec<_> = self.root_state.available_moves().into_iter().collect();
+        let mut statistics = self.root_children_statistics();
+        statistics.sort_by_key(|(mov, s)| {
+            std::cmp::Reverse((s.visit_count, move_order(&available_moves, mov)))
         });
-        IsmctsHandler {
-            root_state,
-            root_node,
-        }
+        statistics
     }
 
-    pub fn make_move(&mut self, mov: &G::Move) {
-        assert!(
-            self.root_state
-                .available_moves()
-                .into_iter()
-                .any(|m| m == *mov),
-            "Move must be legal"
-        );
-        let node = {
-            let children = self.root_node.children.read().unwrap();
-            let child_node = children.iter().find(|c| c.mov.as_ref() == Some(mov));
-            assert!(child_node.is_some(), "Move must be explored");
-            Arc::clone(child_node.unwrap())
-        };
+    /// Every expanded root move with its evaluation, in the order they were expanded
+    pub fn children_summary(&self) -> Vec<RankedMove<G::Move>> {
+        let parent_visits = self.root_node.statistics.snapshot().visit_count;
+        self.root_node
+            .children
+            .read()
+            .unwrap()
+            .iter()
+            .map(|c| {
+                let s = c.statistics.snapshot();
+                let availability = self.config.ucb_availability.count(&s, parent_visits);
+                let prior = c.prior.load();
+                let MoveStat {
+                    mov,
+                    visits,
+                    mean_reward,
+                } = MoveStat::new(c.mov.clone().unwrap(), &s);
+                let prior = self
+                    .root_noise
+                    .as_ref()
+                    .map_or(prior, |noise| noise.mix(&mov, prior));
+                RankedMove {
+                    mov,
+                    visits,
+                    mean_reward,
+                    ucb1: self.config.selection_score(&s, availability, 0.0, prior),
+                    availability: s.availability_count,
+                }
+            })
+            .collect()
+    }
 
-        self.root_state.make_move(mov);
-        self.root_node = node;
+    /// Mean reward of each expanded root move, without any exploration term. Unvisited moves get
+    /// negative infinity rather than `0 / 0`, whose NaN would compare as the largest value, so
+    /// picking the highest value never picks an unvisited move.
+    pub fn exploitation_value(&self) -> Vec<(G::Move, f64)> {
+        self.root_children_statistics()
+            .into_iter()
+            .map(|(mov, s)| {
+                let value = if s.visit_count > 0 {
+                    s.mean()
+                } else {
+                    f64::NEG_INFINITY
+                };
+                (mov, value)
+            })
+            .collect()
     }
 
-    pub fn run_iterations(&mut self, n_threads: usize, n_iterations_per_thread: usize) {
-        spawn_n_threads(n_threads, |_| {
-            ismcts_work_thread_iterations(
-                self.root_state.clone(),
-                Arc::clone(&self.root_node),
-                n_iterations_per_thread,
-            )
-        });
+    /// Every expanded root move with its evaluation, best first: most visited, with ties going
+    /// to the move listed first by the root's available moves, the same as `best_move` ranks them
+    pub fn ranked_moves(&self) -> Vec<RankedMove<G::Move>> {
+        let available_moves: Vec<_> = self.root_state.available_moves().into_iter().collect();
+        let mut moves = self.children_summary();
+        moves.sort_by_key(|m| std::cmp::Reverse((m.visits, move_order(&available_moves, &m.mov))));
+        moves
     }
 
-    pub fn run_timed(&mut self, n_threads: usize, time: Duration) {
-        spawn_n_threads(n_threads, |_| {
-            ismcts_work_thread_timed(self.root_state.clone(), Arc::clone(&self.root_node), time)
-        });
+    /// Move, visit count, availability count and the share of iterations in which the move was
+    /// chosen when it was legal (0 if it never was) of each root child. A low share for a move
+    /// that was often available means the search rarely found it worth playing.
+    pub fn availability_report(&self) -> Vec<(G::Move, usize, usize, f64)> {
+        self.root_children_statistics()
+            .into_iter()
+            .map(|(mov, s)| {
+                let ratio = if s.availability_count > 0 {
+                    s.visit_count as f64 / s.availability_count as f64
+                } else {
+                    0.0
+                };
+                (mov, s.visit_count, s.availability_count, ratio)
+            })
+            .collect()
     }
 
-    pub fn best_move(&self) -> Option<G::Move> {
-        let children = self.root_node.children.read().unwrap();
-        children
+    /// Training sample of the current search, to be collected before each `make_move` of a
+    /// self-play game. The visit distribution is uniform if nothing has been searched yet.
+    pub fn record_policy_target(&self) -> PolicyTarget<G::Move, G::PlayerTag> {
+        PolicyTarget {
+            state_key: self.root_state.state_hash(),
+            player: self.root_state.current_player(),
+            visit_distribution: self.visit_distribution(),
+            result: None,
+        }
+    }
+
+    /// Share of the root's visits of each available move in `available_moves` order, uniform if
+    /// nothing has been searched yet
+    fn visit_distribution(&self) -> Vec<(G::Move, f64)> {
+        let statistics = self.root_children_statistics();
+        let visits: Vec<_> = self
+            .root_state
+            .available_moves()
+            .into_iter()
+            .map(|mov| {
+                let visits = statistics
+                    .iter()
+                    .find(|(m, _)| *m == mov)
+                    .map_or(0, |(_, s)| s.visit_count);
+                (mov, visits)
+            })
+            .collect();
+        let total: usize = visits.iter().map(|(_, v)| v).sum();
+        let n_moves = visits.len() as f64;
+        visits
+            .into_iter()
+            .map(|(mov, v)| {
+                let share = if total > 0 {
+                    v as f64 / total as f64
+                } else {
+                    1.0 / n_moves
+                };
+                (mov, share)
+            })
+            .collect()
+    }
+
+    fn root_children_statistics(&self) -> Vec<(G::Move, NodeStatistics)> {
+        self.root_node
+            .children
+            .read()
+            .unwrap()
+            .iter()
+            .map(|c| (c.mov.clone().unwrap(), c.statistics.snapshot()))
+            .collect()
+    }
+
+    /// Snapshot of the root's children, sorted descending by `key`
+    pub fn root_children_sorted_by<F, K>(&self, mut key: F) -> Vec<ChildInfo<G>>
+    where
+        F: FnMut(&ChildInfo<G>) -> K,
+        K: Ord,
+    {
+        let mut children: Vec<_> = self
+            .root_node
+            .children
+            .read()
+            .unwrap()
             .iter()
-            .max_by_key(|c| c.statistics.read().unwrap().visit_count)
-            .map(|c| c.mov.clone().unwrap())
+            .map(|c| ChildInfo::new(c, 1))
+            .collect();
+        children.sort_by_key(|c| std::cmp::Reverse(key(c)));
+        children
+    }
+
+    /// Lazily visit every node in the tree, breadth first from the root
+    pub fn nodes(&self) -> impl Iterator<Item = ChildInfo<G>> + '_ {
+        NodeIter {
+            queue: std::iter::once((Arc::clone(&self.root_node), 0)).collect(),
+        }
+    }
+
+    /// Number of nodes at each depth, with the root at depth 0
+    pub fn depth_histogram(&self) -> Vec<usize> {
+        let mut histogram = Vec::new();
+        self.root_node.for_each_descendant(|_, depth| {
+            if histogram.len() <= depth {
+                histogram.resize(depth + 1, 0);
+            }
+            histogram[depth] += 1;
+        });
+        histogram
     }
 
     pub fn debug_select(&self) {
         let mut node = Arc::clone(&self.root_node);
         let mut state = self.root_state.clone();
-        let mut available_moves: Vec<_> = state.available_moves().into_iter().collect();
+        let mut available_moves: Vec<_> = state
+            .prune_moves(state.available_moves())
+            .into_iter()
+            .collect();
         let mut depth = 0;
         while !available_moves.is_empty()
             && node
-                .untried_moves(&available_moves)
+                .untried_moves(&available_moves, Access::Shared)
                 .into_iter()
                 .next()
                 .is_none()
         {
             println!("DEPTH {}", depth);
             dbg!(&node.mov);
-            dbg!(&node.statistics.read().unwrap());
+            dbg!(&node.statistics.snapshot());
 
-            node = node.select_child(&available_moves).unwrap();
+            node = node
+                .select_child(&available_moves, &self.search_run(1), None, None)
+                .unwrap();
             state.make_move(&node.mov.clone().unwrap());
-            available_moves = state.available_moves().into_iter().collect();
+            available_moves = state
+                .prune_moves(state.available_moves())
+                .into_iter()
+                .collect();
             depth += 1;
         }
     }
@@ -222,12 +3532,13 @@ impl<G: Game> IsmctsHandler<G> {
             .iter()
             .cloned()
             .collect();
-        children.sort_by_key(|c| c.statistics.read().unwrap().visit_count);
+        children.sort_by_key(|c| c.statistics.snapshot().visit_count);
         for c in children {
-            let statistics = c.statistics.read().unwrap();
+            let statistics = c.statistics.snapshot();
             dbg!(&c.mov);
-            dbg!(&*statistics);
-            dbg!(statistics.ucb1());
+            dbg!(&statistics);
+            dbg!(statistics.ucb1(self.config.exploration));
+            dbg!(statistics.ucb1_tuned());
             println!();
         }
     }
@@ -238,18 +3549,32 @@ impl<G: Game> IsmctsHandler<G> {
             .read()
             .unwrap()
             .iter()
-            .map(|c| c.statistics.read().unwrap().visit_count)
+            .map(|c| c.statistics.snapshot().visit_count)
             .max()
             .unwrap_or_default()
     }
 
+    /// Fraction of the root's visits which ended in a draw by `Game::outcome`
+    pub fn draw_rate(&self) -> f64 {
+        let (draws, visits) = self
+            .root_children_statistics()
+            .iter()
+            .fold((0.0, 0), |(draws, visits), (_, s)| {
+                (draws + s.draws, visits + s.visit_count)
+            });
+        if visits == 0 {
+            return 0.0;
+        }
+        draws / visits as f64
+    }
+
     pub fn total_visits(&self) -> usize {
         self.root_node
             .children
             .read()
             .unwrap()
             .iter()
-            .map(|c| c.statistics.read().unwrap().visit_count)
+            .map(|c| c.statistics.snapshot().visit_count)
             .sum::<usize>()
     }
 
@@ -262,84 +3587,798 @@ impl<G: Game> IsmctsHandler<G> {
     }
 }
 
-fn ismcts_one_iteration<G: Game>(mut state: G, mut node: Arc<Node<G>>) {
-    let mut rng = thread_rng();
+/// Settings shared by every iteration of one call to a `run_*` method
+struct SearchRun<'a, G: Game> {
+    config: &'a SearchConfig<G>,
+    n_threads: usize,
+    /// Move which every iteration starts with
+    forced_move: Option<&'a G::Move>,
+    completed_iterations: &'a AtomicUsize,
+    result_counts: &'a Mutex<BTreeMap<OrderedFloat<f64>, usize>>,
+    transpositions: &'a Mutex<HashMap<u64, SharedStatistics>>,
+    seed: Option<u64>,
+    /// Timed searches end early once this is set
+    stop: Option<&'a AtomicBool>,
+    node_count: &'a AtomicUsize,
+    pruning: &'a Mutex<()>,
+    root_noise: Option<&'a RootNoise<G::Move>>,
+    access: Access,
+}
+
+impl<G: Game> SearchRun<'_, G> {
+    /// Random number generator for the worker thread numbered `thread_index`
+    fn rng(&self, thread_index: usize) -> StdRng {
+        match self.seed {
+            Some(seed) => StdRng::seed_from_u64(seed ^ thread_index as u64),
+            None => StdRng::from_rng(thread_rng()).unwrap(),
+        }
+    }
+}
+
+/// Choose one of `legal_moves` for a later move of a simultaneous round, from the statistics of
+/// each move summed over `level`, the nodes reached by every combination of the round's earlier
+/// moves. Moves never visited anywhere in `level` are tried first.
+fn decoupled_choice<G: Game>(
+    level: &[Arc<Node<G>>],
+    legal_moves: &[G::Move],
+    config: &SearchConfig<G>,
+    access: Access,
+    rng: &mut StdRng,
+) -> G::Move {
+    let mut statistics = vec![NodeStatistics::default(); legal_moves.len()];
+    // The same in every node of `level`, since they are all the same player's choice
+    let mut priors = vec![1.0; legal_moves.len()];
+    let mut availability = 0;
+    for node in level {
+        availability += node.statistics.selection_snapshot().visit_count;
+        for child in node.children.read_with(access).unwrap().iter() {
+            let mov = child.mov.as_ref().unwrap();
+            if let Some(i) = legal_moves.iter().position(|m| m == mov) {
+                priors[i] = child.prior.load();
+                let child_statistics = child.statistics.selection_snapshot();
+                statistics[i].visit_count += child_statistics.visit_count;
+                statistics[i].reward += child_statistics.reward;
+                statistics[i].reward_sq += child_statistics.reward_sq;
+            }
+        }
+    }
+    for s in &mut statistics {
+        s.availability_count = availability;
+    }
+
+    let untried: Vec<_> = (0..legal_moves.len())
+        .filter(|&i| statistics[i].visit_count == 0)
+        .collect();
+    let choice = match untried.choose(rng) {
+        Some(&i) => i,
+        None => (0..legal_moves.len())
+            .max_by_key(|&i| {
+                let score = config.selection.score_with_virtual_loss(
+                    config.exploration,
+                    &statistics[i],
+                    availability,
+                    0.0,
+                    priors[i],
+                );
+                (
+                    OrderedFloat::from(score),
+                    move_order(legal_moves, &legal_moves[i]),
+                )
+            })
+            .unwrap(),
+    };
+    legal_moves[choice].clone()
+}
+
+/// Returns the moves made on `state`, with the players who made them, when they are needed for
+/// RAVE or to unmake them afterwards
+fn ismcts_one_iteration<G: Game>(
+    state: &mut G,
+    mut node: Arc<Node<G>>,
+    run: &SearchRun<G>,
+    rng: &mut StdRng,
+) -> Vec<(G::PlayerTag, G::Move)> {
+    let config = run.config;
+    let mut forced_move = run.forced_move;
+    let deadline = config.iteration_timeout.map(|t| Instant::now() + t);
+    let observer = state.current_player();
 
     // Determinize
-    state.randomize_determination(state.current_player());
+    match &config.fixed_determination {
+        Some(determination) => state.reset_to(determination),
+        None => state.randomize_determination(observer, rng),
+    }
 
     // Select
     let mut available_moves: Vec<_>;
     let mut untried_moves;
+    // Depth of the first node chosen by `select_child`, and how many were chosen
+    let mut first_selected_depth: usize = 1;
+    let mut n_selected = 0;
+    // Every move of the simulation with the player who made it, when needed for RAVE or unmaking
+    let recording = config.rave.is_some() || state.can_unmake_moves();
+    let mut played = Vec::new();
+    let mut record = |player: G::PlayerTag, mov: &G::Move| {
+        if recording {
+            played.push((player, mov.clone()));
+        }
+    };
+    // Node the current simultaneous round started from, and how many of its moves were made
+    let mut round: Option<(Arc<Node<G>>, usize)> = None;
     loop {
-        available_moves = state.available_moves().into_iter().collect();
-        untried_moves = node.untried_moves(&available_moves);
-        if available_moves.is_empty() || !untried_moves.is_empty() {
+        while state.is_chance_node() {
+            state.sample_chance(rng);
+        }
+        available_moves = state
+            .prune_moves(state.available_moves())
+            .into_iter()
+            .collect();
+        untried_moves = node.untried_moves(&available_moves, run.access);
+        if !state.is_simultaneous() {
+            round = Some((Arc::clone(&node), 0));
+        }
+        // The forced move is skipped in determinizations where it isn't legal, and is played even
+        // if `Game::prune_moves` would leave it out
+        if let Some(m) = forced_move
+            .take()
+            .filter(|m| state.available_moves().into_iter().any(|a| a == **m))
+        {
+            if let Some(child) = node.find_child(m, run.access) {
+                node = child;
+                record(state.current_player(), m);
+                state.make_move(m);
+                first_selected_depth += 1;
+                continue;
+            }
+            untried_moves = vec![m.clone()];
+            break;
+        }
+        if available_moves.is_empty() {
             break;
         }
-        node = node.select_child(&available_moves).unwrap();
+        if let Some((start, n_made)) = round.as_mut().filter(|_| state.is_simultaneous()) {
+            *n_made += 1;
+            let m = decoupled_choice(
+                &start.descendants_at(*n_made, run.access),
+                &available_moves,
+                config,
+                run.access,
+                rng,
+            );
+            let Some(child) = node.find_child(&m, run.access) else {
+                untried_moves = vec![m];
+                break;
+            };
+            node.add_availability(&available_moves, run.access);
+            if config.virtual_loss != VirtualLoss::Disabled {
+                run.access
+                    .add(&child.statistics.in_flight, 1, Ordering::Relaxed);
+            }
+            node = child;
+            record(state.current_player(), &m);
+            state.make_move(&m);
+            n_selected += 1;
+            continue;
+        }
+        if let Some(widening) = &config.progressive_widening {
+            let n_expanded = available_moves.len() - untried_moves.len();
+            let visit_count = node.statistics.selection_snapshot().visit_count;
+            if n_expanded > 0 && n_expanded >= widening.max_children(visit_count) {
+                untried_moves.clear();
+            }
+        }
+        let unexplored_score = match (
+            untried_moves.is_empty(),
+            config.fpu,
+            config.pessimistic_expansion,
+        ) {
+            (true, _, _) => None,
+            (false, Some(fpu), _) => Some(fpu),
+            (false, None, false) => {
+                node.add_availability(&available_moves, run.access);
+                break;
+            }
+            (false, None, true) => {
+                match node.pessimistic_unexplored_score(
+                    &available_moves,
+                    config.exploration,
+                    run.access,
+                ) {
+                    Some(score) => Some(score),
+                    None => {
+                        node.add_availability(&available_moves, run.access);
+                        break;
+                    }
+                }
+            }
+        };
+        match node.select_child(&available_moves, run, unexplored_score, None) {
+            Some(child) => node = child,
+            None => break,
+        }
+        record(state.current_player(), node.mov.as_ref().unwrap());
         state.make_move(&node.mov.clone().unwrap());
+        n_selected += 1;
     }
+    let mut depth = first_selected_depth - 1 + n_selected;
 
     //Expand
-    if let Some(m) = untried_moves.into_iter().choose(&mut rng) {
+    let mut n_rollouts = 1;
+    if let Some(m) = choose_expansion(state, &untried_moves, rng) {
         let player_tag = state.current_player();
+        let priors = config.selection.uses_priors().then(|| state.move_priors());
+        // Prior and statistics of a new child for `mov`, whose position has `hash`
+        let new_child = |mov: G::Move, hash: Option<u64>| {
+            let prior = priors.as_ref().map_or(1.0, |priors| {
+                priors
+                    .iter()
+                    .find(|(p, _)| *p == mov)
+                    .map_or(0.0, |(_, p)| *p)
+            });
+            let statistics = match hash {
+                Some(hash) if config.transposition_table => {
+                    Arc::clone(run.transpositions.lock().unwrap().entry(hash).or_default())
+                }
+                _ => Default::default(),
+            };
+            (mov, prior, statistics)
+        };
+        let siblings: Vec<_> = match config.expansion {
+            ExpansionPolicy::Single => Vec::new(),
+            ExpansionPolicy::Full => untried_moves
+                .into_iter()
+                .filter(|u| *u != m)
+                .map(|u| {
+                    let hash = if config.transposition_table {
+                        let mut after = state.clone();
+                        after.make_move(&u);
+                        after.state_hash()
+                    } else {
+                        None
+                    };
+                    new_child(u, hash)
+                })
+                .collect(),
+        };
+        record(player_tag, &m);
         state.make_move(&m);
-        node = node.add_child(m, player_tag);
+        let mut new_children = vec![new_child(m, state.state_hash())];
+        new_children.extend(siblings);
+        #[cfg(test)]
+        if let Some(barrier) = &config.expansion_barrier {
+            barrier.wait();
+        }
+        node = node
+            .add_children(new_children, player_tag, run.node_count, run.access)
+            .swap_remove(0);
+        n_rollouts = config.warmup_rollouts.max(1);
+        depth += 1;
+    }
+    // A terminal node's result is backed up as it is, without repeating it in extra rollouts.
+    // Its state is checked each time, since other determinizations may not be over.
+    let terminal = state.result(state.current_player()).is_some();
+    if terminal {
+        node.terminal.store(true, Ordering::Relaxed);
+        n_rollouts = 1;
     }
 
     //Simulate
-    state.random_rollout();
+    let mut extra_leaves: Vec<G> = (1..n_rollouts)
+        .map(|_| {
+            let mut leaf = state.clone();
+            rollout(&mut leaf, config, deadline, rng, None);
+            leaf
+        })
+        .collect();
+    extra_leaves.extend((1..config.determinizations_per_iter).map(|_| {
+        let mut leaf = state.clone();
+        if config.fixed_determination.is_none() {
+            leaf.randomize_determination(observer, rng);
+        }
+        rollout(&mut leaf, config, deadline, rng, None);
+        leaf
+    }));
+    let history = if recording { Some(&mut played) } else { None };
+    if terminal {
+        // Nothing to simulate
+    } else if config.leaf_parallel > 1 {
+        // Roll out copies of the leaf on other threads while this one rolls out the original
+        let copies: Vec<_> = (1..config.leaf_parallel)
+            .map(|_| (state.clone(), StdRng::seed_from_u64(rng.gen())))
+            .collect();
+        #[cfg(all(feature = "threads", not(target_arch = "wasm32")))]
+        let parallel_leaves = thread::scope(|s| {
+            let handles: Vec<_> = copies
+                .into_iter()
+                .map(|(mut leaf, mut leaf_rng)| {
+                    s.spawn(move |_| {
+                        rollout(&mut leaf, config, deadline, &mut leaf_rng, None);
+                        leaf
+                    })
+                })
+                .collect();
+            rollout(state, config, deadline, rng, history);
+            handles
+                .into_iter()
+                .map(|h| h.join().unwrap())
+                .collect::<Vec<_>>()
+        })
+        .unwrap();
+        #[cfg(not(all(feature = "threads", not(target_arch = "wasm32"))))]
+        let parallel_leaves: Vec<_> = {
+            rollout(state, config, deadline, rng, history);
+            copies
+                .into_iter()
+                .map(|(mut leaf, mut leaf_rng)| {
+                    rollout(&mut leaf, config, deadline, &mut leaf_rng, None);
+                    leaf
+                })
+                .collect()
+        };
+        extra_leaves.extend(parallel_leaves);
+    } else {
+        rollout(state, config, deadline, rng, history);
+    }
+    let leaves = if extra_leaves.is_empty() {
+        std::slice::from_ref(&*state)
+    } else {
+        extra_leaves.push(state.clone());
+        &extra_leaves
+    };
+
+    if config.record_result_distribution {
+        let mut counts = run.result_counts.lock().unwrap();
+        for result in leaves
+            .iter()
+            .filter_map(|leaf| config.result(leaf, observer))
+        {
+            *counts.entry(OrderedFloat::from(result)).or_insert(0) += 1;
+        }
+    }
 
     //Backprop
     let mut backprop_node = node;
+    let track_in_flight = config.virtual_loss != VirtualLoss::Disabled;
+    let mut distance = 0;
     loop {
-        backprop_node.update(&state);
-        let parent = backprop_node.parent.as_ref().and_then(Weak::upgrade);
+        if config.backup_depth.is_none_or(|d| distance <= d) {
+            backprop_node.update(leaves, config, distance, run.access);
+            if config.rave.is_some() {
+                backprop_node.update_amaf(&played[depth..], leaves, config, run.access);
+            }
+        } else if !track_in_flight {
+            break;
+        }
+        distance += 1;
+        // Virtual losses are removed all the way up regardless of `backup_depth`
+        if track_in_flight
+            && depth >= first_selected_depth
+            && depth < first_selected_depth + n_selected
+        {
+            backprop_node
+                .statistics
+                .in_flight
+                .fetch_sub(1, Ordering::Relaxed);
+        }
+        depth = depth.saturating_sub(1);
+        let parent = backprop_node
+            .parent
+            .read_with(run.access)
+            .unwrap()
+            .as_ref()
+            .and_then(Weak::upgrade);
         if let Some(n) = parent {
             backprop_node = n;
         } else {
             break;
         }
     }
+    played
+}
+
+/// Tie-break for `max_by_key`, so that among equal keys the move listed first by `moves` wins,
+/// and moves missing from `moves` lose to every listed move
+fn move_order<M: PartialEq>(moves: &[M], mov: &M) -> std::cmp::Reverse<usize> {
+    std::cmp::Reverse(moves.iter().position(|m| m == mov).unwrap_or(usize::MAX))
+}
+
+fn normal_cdf(x: f64) -> f64 {
+    // Abramowitz and Stegun 7.1.26, absolute error below 1.5e-7
+    let z = x.abs() / std::f64::consts::SQRT_2;
+    let t = 1.0 / (1.0 + 0.327_591_1 * z);
+    let poly = t
+        * (0.254_829_592
+            + t * (-0.284_496_736
+                + t * (1.421_413_741 + t * (-1.453_152_027 + t * 1.061_405_429))));
+    let erf = 1.0 - poly * (-z * z).exp();
+    if x >= 0.0 {
+        0.5 * (1.0 + erf)
+    } else {
+        0.5 * (1.0 - erf)
+    }
+}
+
+/// Breadth first search for a node whose position hashes to `target`, replaying moves from `state`
+fn find_node_by_hash<G: Game>(
+    state: &G,
+    node: &Arc<Node<G>>,
+    target: u64,
+    max_depth: usize,
+) -> Option<Arc<Node<G>>> {
+    let mut queue = std::collections::VecDeque::new();
+    queue.push_back((state.clone(), Arc::clone(node), 0));
+    while let Some((state, node, depth)) = queue.pop_front() {
+        if state.state_hash() == Some(target) {
+            return Some(node);
+        }
+        if depth >= max_depth {
+            continue;
+        }
+        let available_moves: Vec<_> = state.available_moves().into_iter().collect();
+        for child in node.children.read().unwrap().iter() {
+            let mov = child.mov.as_ref().unwrap();
+            // Children were expanded in other determinizations and may not be legal here
+            if available_moves.contains(mov) {
+                let mut child_state = state.clone();
+                child_state.make_move(mov);
+                queue.push_back((child_state, Arc::clone(child), depth + 1));
+            }
+        }
+    }
+    None
+}
+
+/// Untried move to expand, chosen randomly among those of the highest `Game::move_priority`
+fn choose_expansion<G: Game, R: Rng + ?Sized>(
+    state: &G,
+    untried_moves: &[G::Move],
+    rng: &mut R,
+) -> Option<G::Move> {
+    let priorities: Vec<_> = untried_moves
+        .iter()
+        .map(|m| state.move_priority(m))
+        .collect();
+    let highest = priorities.iter().max()?;
+    let candidates: Vec<_> = untried_moves
+        .iter()
+        .zip(&priorities)
+        .filter(|(_, p)| *p == highest)
+        .map(|(m, _)| m)
+        .collect();
+    candidates.choose(rng).map(|m| (*m).clone())
+}
+
+/// Whether the most visited of the root moves with `visits` leads the runner-up by at least
+/// `min_gap` and by more than the iterations left of `max_time`, at the rate of `iterations`
+/// in `elapsed`, see `IsmctsHandler::run_until_confident`
+fn best_move_decided(
+    mut visits: Vec<usize>,
+    iterations: usize,
+    elapsed: Duration,
+    max_time: Duration,
+    min_gap: usize,
+) -> bool {
+    let remaining_iterations =
+        iterations as f64 / elapsed.as_secs_f64() * max_time.saturating_sub(elapsed).as_secs_f64();
+    visits.sort_unstable_by(|a, b| b.cmp(a));
+    let gap = match visits.as_slice() {
+        [] => 0,
+        [top] => *top,
+        [top, second, ..] => top - second,
+    };
+    gap >= min_gap && gap as f64 > remaining_iterations
+}
+
+/// Branching factor which `run_managed` gives an even share of the remaining time
+const MANAGED_TYPICAL_BRANCHING: f64 = 16.0;
+
+/// Time for one move out of `moves_left` sharing `remaining`. An even share is scaled by the
+/// square root of `branching_hint` relative to `MANAGED_TYPICAL_BRANCHING`, but kept between
+/// half and three times the even share, and to at most half of `remaining`.
+fn managed_time(remaining: Duration, moves_left: usize, branching_hint: usize) -> Duration {
+    let even_share = remaining.as_secs_f64() / moves_left.max(1) as f64;
+    let scale = (branching_hint.max(1) as f64 / MANAGED_TYPICAL_BRANCHING)
+        .sqrt()
+        .clamp(0.5, 3.0);
+    Duration::from_secs_f64((even_share * scale).min(remaining.as_secs_f64() / 2.0))
+}
+
+/// `Game::random_rollout`'s default: play `state` until the game ends, or until `max_moves` moves
+/// and chance outcomes have been played, following `Game::rollout_policy` and sampling chance
+/// nodes. Games overriding `random_rollout` to add to it, e.g. to count rollouts, can call this.
+pub fn play_rollout<G: Game, R: Rng + ?Sized>(state: &mut G, rng: &mut R, max_moves: usize) {
+    let mut n_moves = 0;
+    while state.result(state.current_player()).is_none() {
+        if n_moves >= max_moves {
+            break;
+        }
+        n_moves += 1;
+        if state.is_chance_node() {
+            state.sample_chance(rng);
+            continue;
+        }
+        let mov = state
+            .rollout_policy(rng)
+            .or_else(|| state.available_moves().into_iter().choose(rng));
+        if let Some(m) = mov {
+            state.make_move(&m);
+        } else {
+            break;
+        }
+    }
+}
+
+/// How many rollout moves are made between checks of the iteration deadline
+const ROLLOUT_DEADLINE_CHECK_INTERVAL: usize = 16;
+
+fn rollout<G: Game>(
+    state: &mut G,
+    config: &SearchConfig<G>,
+    deadline: Option<Instant>,
+    rng: &mut StdRng,
+    mut history: Option<&mut Vec<(G::PlayerTag, G::Move)>>,
+) {
+    let sample_size = config.rollout_move_sample_size.filter(|&k| k > 0);
+    if sample_size.is_none()
+        && matches!(config.rollout, RolloutPolicy::Game)
+        && config.max_rollout_depth.is_none()
+        && deadline.is_none()
+        && history.is_none()
+    {
+        return state.random_rollout(rng, config.max_rollout_moves);
+    }
+
+    let mut n_moves = 0;
+    // Moves and chance outcomes, for `max_rollout_moves` and the deadline checks
+    let mut n_steps = 0;
+    while state.result(state.current_player()).is_none() {
+        if config
+            .max_rollout_depth
+            .is_some_and(|depth| n_moves >= depth)
+            || n_steps >= config.max_rollout_moves
+        {
+            break;
+        }
+        if n_steps % ROLLOUT_DEADLINE_CHECK_INTERVAL == 0
+            && deadline.is_some_and(|d| Instant::now() > d)
+        {
+            break;
+        }
+        n_steps += 1;
+        if state.is_chance_node() {
+            state.sample_chance(rng);
+            continue;
+        }
+
+        let mov = config.rollout.choose(state, rng).or_else(|| {
+            let moves = state.available_moves().into_iter();
+            match sample_size {
+                Some(k) => {
+                    let sampled: Vec<_> = moves
+                        .choose_multiple(rng, k)
+                        .into_iter()
+                        .map(|m| (state.move_priority(&m), m))
+                        .collect();
+                    let best = sampled.iter().map(|&(priority, _)| priority).max()?;
+                    sampled
+                        .into_iter()
+                        .filter(|&(priority, _)| priority == best)
+                        .map(|(_, m)| m)
+                        .choose(rng)
+                }
+                None => moves.choose(rng),
+            }
+        });
+        if let Some(m) = mov {
+            if let Some(history) = history.as_mut() {
+                history.push((state.current_player(), m.clone()));
+            }
+            state.make_move(&m);
+        } else {
+            break;
+        }
+        n_moves += 1;
+    }
+}
+
+/// Scratch states for a single worker thread, kept between iterations with
+/// `SearchConfig::reuse_state_buffers` or `Game::can_unmake_moves`
+struct StatePool<G: Game> {
+    reuse: bool,
+    free: Vec<G>,
+}
+
+impl<G: Game> StatePool<G> {
+    fn new(reuse: bool) -> Self {
+        StatePool {
+            reuse,
+            free: Vec::new(),
+        }
+    }
+
+    fn take(&mut self, root_state: &G) -> G {
+        match self.free.pop() {
+            // Already back at the root position
+            Some(state) if state.can_unmake_moves() => state,
+            Some(mut state) => {
+                state.reset_to(root_state);
+                state
+            }
+            None => root_state.clone(),
+        }
+    }
+
+    /// Return `state` after an iteration which made `played` on it
+    fn give(&mut self, mut state: G, played: &[(G::PlayerTag, G::Move)]) {
+        if state.can_unmake_moves() {
+            for (_, mov) in played.iter().rev() {
+                state.unmake_move(mov);
+            }
+            self.free.push(state);
+        } else if self.reuse {
+            self.free.push(state);
+        }
+    }
 }
 
 fn ismcts_work_thread_iterations<G: Game>(
     root_state: G,
     root_node: Arc<Node<G>>,
+    run: &SearchRun<G>,
+    thread_index: usize,
     n_iterations: usize,
 ) {
+    let mut pool = StatePool::new(run.config.reuse_state_buffers);
+    let mut rng = run.rng(thread_index);
     for _i in 0..n_iterations {
-        let state = root_state.clone();
-        let node = Arc::clone(&root_node);
-
-        ismcts_one_iteration(state, node);
+        ismcts_work_iteration(&root_state, &root_node, run, &mut pool, &mut rng);
     }
 }
 
-fn ismcts_work_thread_timed<G: Game>(root_state: G, root_node: Arc<Node<G>>, time: Duration) {
+fn ismcts_work_thread_timed<G: Game>(
+    root_state: G,
+    root_node: Arc<Node<G>>,
+    run: &SearchRun<G>,
+    thread_index: usize,
+    time: Duration,
+) {
+    let mut pool = StatePool::new(run.config.reuse_state_buffers);
+    let mut rng = run.rng(thread_index);
     let start = Instant::now();
-    loop {
-        let duration = start.elapsed();
-        if duration > time {
+    let check_every = run.config.time_check_every.max(1);
+    for i in 0.. {
+        if i % check_every == 0
+            && (start.elapsed() > time || run.stop.is_some_and(|stop| stop.load(Ordering::Relaxed)))
+        {
             break;
         }
-        let state = root_state.clone();
-        let node = Arc::clone(&root_node);
+        ismcts_work_iteration(&root_state, &root_node, run, &mut pool, &mut rng);
+    }
+}
+
+/// One iteration of a worker thread, counted towards the run
+fn ismcts_work_iteration<G: Game>(
+    root_state: &G,
+    root_node: &Arc<Node<G>>,
+    run: &SearchRun<G>,
+    pool: &mut StatePool<G>,
+    rng: &mut StdRng,
+) {
+    let mut state = pool.take(root_state);
+    let played = ismcts_one_iteration(&mut state, Arc::clone(root_node), run, rng);
+    pool.give(state, &played);
+    run.completed_iterations.fetch_add(1, Ordering::Relaxed);
+    limit_tree_size(root_node, run);
+}
+
+/// Prune the tree for `SearchConfig::max_nodes` if it has grown too large,
+/// unless another thread is already doing so
+fn limit_tree_size<G: Game>(root_node: &Arc<Node<G>>, run: &SearchRun<G>) {
+    let max_nodes = match run.config.max_nodes {
+        Some(max_nodes) if run.node_count.load(Ordering::Relaxed) > max_nodes => max_nodes,
+        _ => return,
+    };
+    if let Ok(_pruning) = run.pruning.try_lock() {
+        prune(
+            root_node,
+            max_nodes * 3 / 4,
+            run.node_count,
+            run.transpositions,
+        );
+    }
+}
+
+/// Remove the least visited subtrees below the root's children until at most `target` nodes
+/// remain, along with the transpositions only they shared
+fn prune<G: Game>(
+    root_node: &Arc<Node<G>>,
+    target: usize,
+    node_count: &AtomicUsize,
+    transpositions: &Mutex<HashMap<u64, SharedStatistics>>,
+) {
+    let mut candidates = Vec::new();
+    root_node.for_each_descendant(|node, depth| {
+        if depth >= 2 {
+            candidates.push(Arc::clone(node));
+        }
+    });
+    candidates.sort_by_cached_key(|node| node.statistics.visit_count.load(Ordering::Relaxed));
 
-        ismcts_one_iteration(state, node);
+    let mut removed = HashSet::new();
+    for node in candidates {
+        if node_count.load(Ordering::Relaxed) <= target {
+            break;
+        }
+        if removed.contains(&Arc::as_ptr(&node)) {
+            continue;
+        }
+        let parent = node.parent.read().unwrap().as_ref().and_then(Weak::upgrade);
+        if let Some(parent) = parent {
+            parent
+                .children
+                .write()
+                .unwrap()
+                .retain(|c| !Arc::ptr_eq(c, &node));
+        }
+        let mut size = 0;
+        let mut stack = vec![node];
+        while let Some(n) = stack.pop() {
+            // Iterations still searching the subtree may add children to it, which are either
+            // counted here or not counted at all
+            let children = n.children.read().unwrap();
+            n.pruned.store(true, Ordering::Relaxed);
+            removed.insert(Arc::as_ptr(&n));
+            size += 1;
+            stack.extend(children.iter().cloned());
+        }
+        node_count.fetch_sub(size, Ordering::Relaxed);
     }
+    trim_transpositions(root_node, transpositions);
+}
+
+/// Drop the transpositions which no node of the tree under `root_node` shares anymore.
+/// An iteration about to add a node for a dropped transposition only loses its sharing.
+fn trim_transpositions<G: Game>(
+    root_node: &Arc<Node<G>>,
+    transpositions: &Mutex<HashMap<u64, SharedStatistics>>,
+) {
+    let mut shared = HashSet::new();
+    root_node.for_each_descendant(|node, _| {
+        shared.insert(Arc::as_ptr(&node.statistics));
+    });
+    transpositions
+        .lock()
+        .unwrap()
+        .retain(|_, statistics| shared.contains(&Arc::as_ptr(statistics)));
 }
 
+/// Run `f` on `n_threads` threads, passing each its index.
+/// A single thread runs on the calling thread instead of being spawned.
+#[cfg(all(feature = "threads", not(target_arch = "wasm32")))]
 fn spawn_n_threads<'env, F, T>(n_threads: usize, f: F)
 where
-    F: Copy + FnOnce(&crossbeam::thread::Scope<'env>) -> T + Send + 'env,
+    F: Copy + FnOnce(usize) -> T + Send + 'env,
     T: Send + 'env,
 {
+    if n_threads == 1 {
+        f(0);
+        return;
+    }
     thread::scope(|s| {
-        for _ in 0..n_threads {
-            s.spawn(f);
+        for thread_index in 0..n_threads {
+            s.spawn(move |_| f(thread_index));
         }
     })
     .unwrap();
 }
+
+/// Without threads, run `f` for each of `n_threads` indices in turn on the calling thread
+#[cfg(not(all(feature = "threads", not(target_arch = "wasm32"))))]
+fn spawn_n_threads<F, T>(n_threads: usize, f: F)
+where
+    F: Copy + FnOnce(usize) -> T,
+{
+    for thread_index in 0..n_threads {
+        f(thread_index);
+    }
+}
@@ -0,0 +1,230 @@
+use crate::*;
+
+/// Multiple observer ISMCTS, which keeps a tree for each player. Each player chooses their moves
+/// in their own tree, where the other players' moves appear as `Game::observe_move` shows them,
+/// so that nobody's choices depend on information they don't have.
+///
+/// Virtual loss is the only `SearchConfig` option specific to the search tree which is supported.
+pub struct IsmctsHandlerMo<G: Game> {
+    root_state: G,
+    players: Vec<G::PlayerTag>,
+    /// One root for each of `players`
+    trees: Vec<Arc<Node<G>>>,
+    config: SearchConfig<G>,
+    lifetime_iterations: AtomicUsize,
+    result_counts: Mutex<BTreeMap<OrderedFloat<f64>, usize>>,
+    transpositions: Mutex<HashMap<u64, SharedStatistics>>,
+    node_count: AtomicUsize,
+    pruning: Mutex<()>,
+}
+
+impl<G: Game> IsmctsHandlerMo<G> {
+    /// Search from `root_state` with a tree for each of `players`, which must include every
+    /// player who moves in the game
+    pub fn new(root_state: G, players: Vec<G::PlayerTag>) -> Self {
+        let trees = players.iter().map(|_| Node::new_root()).collect();
+        IsmctsHandlerMo {
+            root_state,
+            node_count: AtomicUsize::new(players.len()),
+            players,
+            trees,
+            config: Default::default(),
+            lifetime_iterations: AtomicUsize::new(0),
+            result_counts: Default::default(),
+            transpositions: Default::default(),
+            pruning: Default::default(),
+        }
+    }
+
+    pub fn config(&self) -> &SearchConfig<G> {
+        &self.config
+    }
+
+    pub fn config_mut(&mut self) -> &mut SearchConfig<G> {
+        &mut self.config
+    }
+
+    /// Iterations completed since construction
+    pub fn lifetime_iterations(&self) -> usize {
+        self.lifetime_iterations.load(Ordering::Relaxed)
+    }
+
+    fn search_run(&self, n_threads: usize) -> SearchRun<'_, G> {
+        self.config.check_reward_bounds();
+        SearchRun {
+            config: &self.config,
+            n_threads,
+            forced_move: None,
+            completed_iterations: &self.lifetime_iterations,
+            result_counts: &self.result_counts,
+            transpositions: &self.transpositions,
+            seed: self
+                .config
+                .seed
+                .map(|seed| seed.wrapping_add(self.lifetime_iterations() as u64)),
+            stop: None,
+            node_count: &self.node_count,
+            pruning: &self.pruning,
+            root_noise: None,
+            access: Access::Shared,
+        }
+    }
+
+    pub fn run_iterations(&self, n_threads: usize, n_iterations_per_thread: usize) {
+        let run = self.search_run(n_threads);
+        spawn_n_threads(n_threads, |thread_index| {
+            let mut rng = run.rng(thread_index);
+            for _ in 0..n_iterations_per_thread {
+                let mut state = self.root_state.clone();
+                mo_iteration(&mut state, &self.players, &self.trees, &run, &mut rng);
+                run.completed_iterations.fetch_add(1, Ordering::Relaxed);
+            }
+        });
+    }
+
+    /// Tree of the player to move at the root
+    fn root_tree(&self) -> &Arc<Node<G>> {
+        &self.trees[player_index(&self.players, self.root_state.current_player())]
+    }
+
+    /// Most visited move of the player to move at the root
+    pub fn best_move(&self) -> Option<G::Move> {
+        let available_moves: Vec<_> = self.root_state.available_moves().into_iter().collect();
+        self.root_tree()
+            .children
+            .read()
+            .unwrap()
+            .iter()
+            .max_by_key(|c| {
+                (
+                    c.statistics.visit_count.load(Ordering::Relaxed),
+                    move_order(&available_moves, c.mov.as_ref().unwrap()),
+                )
+            })
+            .map(|c| c.mov.clone().unwrap())
+    }
+
+    /// Move, visit count and mean reward (0 if unvisited) of each of the root player's moves,
+    /// most visited first
+    pub fn move_statistics(&self) -> Vec<(G::Move, usize, f64)> {
+        let available_moves: Vec<_> = self.root_state.available_moves().into_iter().collect();
+        let mut statistics: Vec<_> = self
+            .root_tree()
+            .children
+            .read()
+            .unwrap()
+            .iter()
+            .map(|c| {
+                let s = c.statistics.snapshot();
+                let mean = if s.visit_count > 0 { s.mean() } else { 0.0 };
+                (c.mov.clone().unwrap(), s.visit_count, mean)
+            })
+            .collect();
+        statistics.sort_by_key(|(mov, visits, _)| {
+            std::cmp::Reverse((*visits, move_order(&available_moves, mov)))
+        });
+        statistics
+    }
+
+    /// Number of nodes in all of the trees, including their roots
+    pub fn node_count(&self) -> usize {
+        self.node_count.load(Ordering::Relaxed)
+    }
+}
+
+fn player_index<P: PartialEq + std::fmt::Debug>(players: &[P], player: P) -> usize {
+    players
+        .iter()
+        .position(|p| *p == player)
+        .unwrap_or_else(|| panic!("{:?} has no search tree", player))
+}
+
+fn mo_iteration<G: Game>(
+    state: &mut G,
+    players: &[G::PlayerTag],
+    trees: &[Arc<Node<G>>],
+    run: &SearchRun<G>,
+    rng: &mut StdRng,
+) {
+    let config = run.config;
+    let observer = state.current_player();
+
+    // Determinize
+    match &config.fixed_determination {
+        Some(determination) => state.reset_to(determination),
+        None => state.randomize_determination(observer, rng),
+    }
+
+    // Select in the mover's tree until a move is expanded, following along in the other trees
+    let mut nodes = trees.to_vec();
+    let mut selected = Vec::new();
+    loop {
+        while state.is_chance_node() {
+            state.sample_chance(rng);
+        }
+        let available_moves: Vec<_> = state
+            .prune_moves(state.available_moves())
+            .into_iter()
+            .collect();
+        if available_moves.is_empty() {
+            break;
+        }
+        let mover = state.current_player();
+        let mover_index = player_index(players, mover);
+        let untried_moves = nodes[mover_index].untried_moves(&available_moves, run.access);
+        let expanding = !untried_moves.is_empty();
+        if expanding {
+            nodes[mover_index].add_availability(&available_moves, run.access);
+        }
+        let mov = match choose_expansion(state, &untried_moves, rng) {
+            Some(m) => m,
+            None => {
+                // Ties are broken randomly, since trees which all favour the same moves can
+                // fall into step with each other, so that one player's choices follow another's
+                let child = nodes[mover_index]
+                    .select_child(&available_moves, run, None, Some(rng.gen()))
+                    .unwrap();
+                let mov = child.mov.clone().unwrap();
+                selected.push(child);
+                mov
+            }
+        };
+
+        for (index, node) in nodes.iter_mut().enumerate() {
+            let seen = if index == mover_index {
+                mov.clone()
+            } else {
+                state.observe_move(&mov, players[index])
+            };
+            *node = match node.find_child(&seen, run.access) {
+                Some(child) => child,
+                None => {
+                    Arc::clone(node).add_child(seen, mover, 1.0, Default::default(), run.node_count)
+                }
+            };
+        }
+        state.make_move(&mov);
+        if expanding {
+            break;
+        }
+    }
+
+    // Simulate
+    rollout(state, config, None, rng, None);
+
+    // Backpropagate through every tree
+    for node in nodes {
+        let mut node = Some(node);
+        let mut distance = 0;
+        while let Some(n) = node {
+            n.update(std::slice::from_ref(state), config, distance, run.access);
+            distance += 1;
+            node = n.parent.read().unwrap().as_ref().and_then(Weak::upgrade);
+        }
+    }
+    if config.virtual_loss != VirtualLoss::Disabled {
+        for node in selected {
+            node.statistics.in_flight.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+}
@@ -0,0 +1,80 @@
+use crate::*;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+
+/// A node of a saved tree, listed after its parent
+#[derive(Serialize, Deserialize)]
+struct SavedNode<M, P> {
+    parent: Option<usize>,
+    mov: Option<M>,
+    player_just_moved: Option<P>,
+    prior: f64,
+    statistics: NodeStatistics,
+}
+
+impl<G: Game> IsmctsHandler<G>
+where
+    G::Move: Serialize + DeserializeOwned,
+    G::PlayerTag: Serialize + DeserializeOwned,
+{
+    /// Write the search tree as JSON. Statistics shared through the transposition table
+    /// are saved separately for each node.
+    pub fn save<W: Write>(&self, writer: W) -> serde_json::Result<()> {
+        let mut nodes = Vec::new();
+        let mut queue = std::collections::VecDeque::new();
+        queue.push_back((Arc::clone(&self.root_node), None));
+        while let Some((node, parent)) = queue.pop_front() {
+            let index = nodes.len();
+            nodes.push(SavedNode {
+                parent,
+                mov: node.mov.clone(),
+                player_just_moved: node.player_just_moved,
+                prior: node.prior.load(),
+                statistics: NodeStatistics {
+                    in_flight: 0,
+                    ..node.statistics.snapshot()
+                },
+            });
+            for child in node.children.read().unwrap().iter() {
+                queue.push_back((Arc::clone(child), Some(index)));
+            }
+        }
+        serde_json::to_writer(writer, &nodes)
+    }
+
+    /// Replace the search tree with one written by `save` for the current root state
+    pub fn load<R: Read>(&mut self, reader: R) -> serde_json::Result<()> {
+        let saved: Vec<SavedNode<G::Move, G::PlayerTag>> = serde_json::from_reader(reader)?;
+        let mut nodes: Vec<Arc<Node<G>>> = Vec::with_capacity(saved.len());
+        for node in saved {
+            let parent = match node.parent {
+                Some(p) if p < nodes.len() => Some(Arc::clone(&nodes[p])),
+                None if nodes.is_empty() => None,
+                _ => return Err(serde::de::Error::custom("node listed before its parent")),
+            };
+            let loaded = Arc::new(Node {
+                mov: node.mov,
+                parent: TreeLock::new(parent.as_ref().map(Arc::downgrade)),
+                children: Default::default(),
+                player_just_moved: node.player_just_moved,
+                prior: AtomicF64::new(node.prior),
+                terminal: Default::default(),
+                statistics: Arc::new(node.statistics.into()),
+                pruned: Default::default(),
+            });
+            if let Some(parent) = parent {
+                parent.children.write().unwrap().push(Arc::clone(&loaded));
+            }
+            nodes.push(loaded);
+        }
+        match nodes.into_iter().next() {
+            Some(root) => {
+                self.root_node = root;
+                self.recount_nodes();
+                Ok(())
+            }
+            None => Err(serde::de::Error::custom("empty tree")),
+        }
+    }
+}
@@ -1,4 +1,5 @@
 use crate::*;
+use std::sync::atomic::AtomicUsize;
 
 #[derive(Clone, Debug, Default)]
 struct TenMoveGame {
@@ -13,7 +14,12 @@ impl Game for TenMoveGame {
 
     type MoveList = Vec<u8>;
 
-    fn randomize_determination(&mut self, _observer: Self::PlayerTag) {}
+    fn randomize_determination<R: Rng + ?Sized>(
+        &mut self,
+        _observer: Self::PlayerTag,
+        _rng: &mut R,
+    ) {
+    }
 
     fn current_player(&self) -> Self::PlayerTag {
         self.moves.len() % 2
@@ -43,43 +49,3568 @@ impl Game for TenMoveGame {
             .last()
             .map(|m| if usize::from(*m) == player { 1.0 } else { 0.0 })
     }
+
+    fn state_hash(&self) -> Option<u64> {
+        use std::hash::{Hash, Hasher};
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        self.moves.hash(&mut hasher);
+        Some(hasher.finish())
+    }
 }
 
-const ITERATIONS: usize = 1000;
+/// Normal play Nim: whoever takes the last object wins
+#[derive(Clone, Debug)]
+struct Nim {
+    heaps: Vec<usize>,
+    player_to_move: usize,
+}
 
-#[test]
-pub fn number_of_children_1_thread() {
-    number_of_children(1);
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct NimMove {
+    heap: usize,
+    amount: usize,
 }
 
-#[test]
-pub fn number_of_children_4_threads() {
-    number_of_children(4);
+impl Nim {
+    fn new(heaps: Vec<usize>) -> Self {
+        Nim {
+            heaps,
+            player_to_move: 0,
+        }
+    }
 }
 
-fn number_of_children(n_threads: usize) {
-    let game = TenMoveGame::default();
-    let mut ismcts = IsmctsHandler::new(game);
-    ismcts.run_iterations(n_threads, ITERATIONS);
-    // ismcts.debug_children();
+impl Game for Nim {
+    type Move = NimMove;
 
-    let children = ismcts.root_node.children.read().unwrap();
+    type PlayerTag = usize;
 
-    let total_iterations = ITERATIONS * n_threads;
-    assert_eq!(10, children.len());
-    assert_eq!(
-        total_iterations,
-        children
+    type MoveList = Vec<NimMove>;
+
+    fn randomize_determination<R: Rng + ?Sized>(
+        &mut self,
+        _observer: Self::PlayerTag,
+        _rng: &mut R,
+    ) {
+    }
+
+    fn current_player(&self) -> Self::PlayerTag {
+        self.player_to_move
+    }
+
+    fn next_player(&self) -> Self::PlayerTag {
+        1 - self.player_to_move
+    }
+
+    fn available_moves(&self) -> Self::MoveList {
+        self.heaps
             .iter()
-            .map(|c| c.statistics.read().unwrap().visit_count)
-            .sum()
-    );
+            .enumerate()
+            .flat_map(|(heap, &n)| (1..=n).map(move |amount| NimMove { heap, amount }))
+            .collect()
+    }
 
-    for child in children.iter() {
-        // assert_eq!(
-        //     total_iterations,
-        //     child.statistics.read().unwrap().availability_count
-        // );
-        assert_eq!(10, child.children.read().unwrap().len());
+    fn make_move(&mut self, mov: &Self::Move) {
+        self.heaps[mov.heap] -= mov.amount;
+        self.player_to_move = self.next_player();
+    }
+
+    fn result(&self, player: Self::PlayerTag) -> Option<f64> {
+        if self.heaps.iter().any(|&n| n > 0) {
+            None
+        } else if player == self.next_player() {
+            Some(1.0)
+        } else {
+            Some(0.0)
+        }
+    }
+
+    fn state_hash(&self) -> Option<u64> {
+        use std::hash::{Hash, Hasher};
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        self.heaps.hash(&mut hasher);
+        self.player_to_move.hash(&mut hasher);
+        Some(hasher.finish())
+    }
+}
+
+/// Move leaving the heaps with a nim-sum of zero, if there is one
+fn optimal_nim_move(nim: &Nim) -> Option<NimMove> {
+    let nim_sum = nim.heaps.iter().fold(0, |acc, n| acc ^ n);
+    nim.heaps
+        .iter()
+        .enumerate()
+        .find(|(_, &n)| n ^ nim_sum < n)
+        .map(|(heap, &n)| NimMove {
+            heap,
+            amount: n - (n ^ nim_sum),
+        })
+}
+
+/// A game which never ends, so rollouts must be cut short
+#[derive(Clone, Debug, Default)]
+struct EndlessGame {
+    turn: usize,
+    /// Every turn is a coin flip instead of a move
+    chance: bool,
+}
+
+impl Game for EndlessGame {
+    type Move = u8;
+
+    type PlayerTag = usize;
+
+    type MoveList = Vec<u8>;
+
+    fn randomize_determination<R: Rng + ?Sized>(
+        &mut self,
+        _observer: Self::PlayerTag,
+        _rng: &mut R,
+    ) {
+    }
+
+    fn current_player(&self) -> Self::PlayerTag {
+        self.turn % 2
+    }
+
+    fn next_player(&self) -> Self::PlayerTag {
+        (self.turn + 1) % 2
+    }
+
+    fn available_moves(&self) -> Self::MoveList {
+        vec![0, 1]
+    }
+
+    fn make_move(&mut self, _mov: &Self::Move) {
+        self.turn += 1;
+    }
+
+    fn result(&self, _player: Self::PlayerTag) -> Option<f64> {
+        None
+    }
+
+    fn evaluate(&self, _player: Self::PlayerTag) -> f64 {
+        0.5
+    }
+
+    fn is_chance_node(&self) -> bool {
+        self.chance
+    }
+
+    fn sample_chance<R: Rng + ?Sized>(&mut self, _rng: &mut R) {
+        self.turn += 1;
+    }
+}
+
+/// What an `Instrumented` game has seen, shared by every copy of it
+#[derive(Debug, Default)]
+struct Counts {
+    clones: AtomicUsize,
+    rollouts: AtomicUsize,
+    /// Threads which determinized a copy
+    threads: Mutex<Vec<std::thread::ThreadId>>,
+}
+
+/// Rollout move of an `Instrumented` game, or `None` to fall back to a random move
+type RolloutMove<G> = fn(&G) -> Option<<G as Game>::Move>;
+
+/// Wraps `game` for tests, counting clones and rollouts and recording the threads which
+/// determinize it. The other fields change how it's played, and otherwise it forwards to `game`.
+#[derive(Debug)]
+struct Instrumented<G: Game> {
+    game: G,
+    counts: Arc<Counts>,
+    /// Score every player by this player's result
+    result_for: Option<G::PlayerTag>,
+    /// Results for losing and winning, scaling those of `game` between 0 and 1
+    rewards: (f64, f64),
+    /// Given the highest `move_priority`
+    priority_move: Option<G::Move>,
+    /// Left out by `prune_moves`
+    pruned_move: Option<G::Move>,
+    rollout: Option<RolloutMove<G>>,
+}
+
+impl<G: Game> Instrumented<G> {
+    fn new(game: G) -> Self {
+        Instrumented {
+            game,
+            counts: Arc::default(),
+            result_for: None,
+            rewards: (0.0, 1.0),
+            priority_move: None,
+            pruned_move: None,
+            rollout: None,
+        }
+    }
+
+    fn scale(&self, reward: f64) -> f64 {
+        let (low, high) = self.rewards;
+        low + (high - low) * reward
+    }
+}
+
+impl<G: Game + Default> Default for Instrumented<G> {
+    fn default() -> Self {
+        Instrumented::new(G::default())
+    }
+}
+
+impl<G: Game> Clone for Instrumented<G> {
+    fn clone(&self) -> Self {
+        self.counts.clones.fetch_add(1, Ordering::SeqCst);
+        Instrumented {
+            game: self.game.clone(),
+            counts: Arc::clone(&self.counts),
+            result_for: self.result_for,
+            rewards: self.rewards,
+            priority_move: self.priority_move.clone(),
+            pruned_move: self.pruned_move.clone(),
+            rollout: self.rollout,
+        }
+    }
+}
+
+impl<G: Game<MoveList = Vec<<G as Game>::Move>>> Game for Instrumented<G> {
+    type Move = G::Move;
+
+    type PlayerTag = G::PlayerTag;
+
+    type MoveList = Vec<G::Move>;
+
+    fn randomize_determination<R: Rng + ?Sized>(&mut self, observer: Self::PlayerTag, rng: &mut R) {
+        self.counts
+            .threads
+            .lock()
+            .unwrap()
+            .push(std::thread::current().id());
+        self.game.randomize_determination(observer, rng)
+    }
+
+    fn current_player(&self) -> Self::PlayerTag {
+        self.game.current_player()
+    }
+
+    fn next_player(&self) -> Self::PlayerTag {
+        self.game.next_player()
+    }
+
+    fn available_moves(&self) -> Self::MoveList {
+        self.game.available_moves()
+    }
+
+    fn make_move(&mut self, mov: &Self::Move) {
+        self.game.make_move(mov)
+    }
+
+    fn result(&self, player: Self::PlayerTag) -> Option<f64> {
+        let player = self.result_for.unwrap_or(player);
+        self.game.result(player).map(|r| self.scale(r))
+    }
+
+    fn outcome(&self, player: Self::PlayerTag) -> Option<Outcome> {
+        self.game.outcome(self.result_for.unwrap_or(player))
+    }
+
+    fn evaluate(&self, player: Self::PlayerTag) -> f64 {
+        self.scale(self.game.evaluate(self.result_for.unwrap_or(player)))
+    }
+
+    fn state_hash(&self) -> Option<u64> {
+        self.game.state_hash()
+    }
+
+    /// Reuses `game`'s buffers without counting a clone
+    fn reset_to(&mut self, source: &Self) {
+        self.game.reset_to(&source.game);
+    }
+
+    fn can_unmake_moves(&self) -> bool {
+        self.game.can_unmake_moves()
+    }
+
+    fn unmake_move(&mut self, mov: &Self::Move) {
+        self.game.unmake_move(mov)
+    }
+
+    fn is_chance_node(&self) -> bool {
+        self.game.is_chance_node()
+    }
+
+    fn sample_chance<R: Rng + ?Sized>(&mut self, rng: &mut R) {
+        self.game.sample_chance(rng)
+    }
+
+    fn rollout_policy<R: Rng + ?Sized>(&self, rng: &mut R) -> Option<Self::Move> {
+        match self.rollout {
+            Some(policy) => policy(&self.game),
+            None => self.game.rollout_policy(rng),
+        }
+    }
+
+    fn prune_moves(&self, moves: Self::MoveList) -> Self::MoveList {
+        let mut moves = self.game.prune_moves(moves);
+        moves.retain(|m| Some(m) != self.pruned_move.as_ref());
+        moves
+    }
+
+    fn move_priority(&self, mov: &Self::Move) -> i32 {
+        if self.priority_move.as_ref() == Some(mov) {
+            1
+        } else {
+            self.game.move_priority(mov)
+        }
+    }
+
+    fn random_rollout<R: Rng + ?Sized>(&mut self, rng: &mut R, max_moves: usize) {
+        self.counts.rollouts.fetch_add(1, Ordering::SeqCst);
+        // Through this wrapper, so that rollouts follow `rollout`
+        play_rollout(self, rng, max_moves)
+    }
+}
+
+/// `TenMoveGame` with a large board to copy, which can unmake its moves if `unmake` is set
+#[derive(Clone, Debug)]
+struct LargeStateGame {
+    game: TenMoveGame,
+    board: Vec<u64>,
+    unmake: bool,
+}
+
+impl LargeStateGame {
+    fn new(unmake: bool) -> Self {
+        LargeStateGame {
+            game: TenMoveGame::default(),
+            board: vec![0; 100_000],
+            unmake,
+        }
+    }
+}
+
+impl Game for LargeStateGame {
+    type Move = u8;
+
+    type PlayerTag = usize;
+
+    type MoveList = Vec<u8>;
+
+    fn randomize_determination<R: Rng + ?Sized>(&mut self, observer: Self::PlayerTag, rng: &mut R) {
+        self.game.randomize_determination(observer, rng)
+    }
+
+    fn current_player(&self) -> Self::PlayerTag {
+        self.game.current_player()
+    }
+
+    fn next_player(&self) -> Self::PlayerTag {
+        self.game.next_player()
+    }
+
+    fn available_moves(&self) -> Self::MoveList {
+        self.game.available_moves()
+    }
+
+    fn make_move(&mut self, mov: &Self::Move) {
+        self.board[self.game.moves.len()] += u64::from(*mov);
+        self.game.make_move(mov)
+    }
+
+    fn result(&self, player: Self::PlayerTag) -> Option<f64> {
+        self.game.result(player)
+    }
+
+    fn can_unmake_moves(&self) -> bool {
+        self.unmake
+    }
+
+    fn unmake_move(&mut self, mov: &Self::Move) {
+        assert_eq!(Some(*mov), self.game.moves.pop());
+        self.board[self.game.moves.len()] -= u64::from(*mov);
+    }
+}
+
+/// Three players each pick 0 or 1 in turn. The last player wins by copying the first,
+/// and the first player wins if the last picks 0, so the first player should pick 0.
+/// The second player wins by picking differently from the last.
+#[derive(Clone, Debug, Default)]
+struct CopycatGame {
+    picks: Vec<u8>,
+}
+
+impl Game for CopycatGame {
+    type Move = u8;
+
+    type PlayerTag = usize;
+
+    type MoveList = Vec<u8>;
+
+    fn randomize_determination<R: Rng + ?Sized>(
+        &mut self,
+        _observer: Self::PlayerTag,
+        _rng: &mut R,
+    ) {
+    }
+
+    fn current_player(&self) -> Self::PlayerTag {
+        self.picks.len() % 3
+    }
+
+    fn next_player(&self) -> Self::PlayerTag {
+        (self.picks.len() + 1) % 3
+    }
+
+    fn available_moves(&self) -> Self::MoveList {
+        if self.picks.len() < 3 {
+            vec![0, 1]
+        } else {
+            Vec::new()
+        }
+    }
+
+    fn make_move(&mut self, mov: &Self::Move) {
+        self.picks.push(*mov);
+    }
+
+    fn result(&self, player: Self::PlayerTag) -> Option<f64> {
+        if self.picks.len() < 3 {
+            return None;
+        }
+        let won = match player {
+            0 => self.picks[2] == 0,
+            1 => self.picks[1] != self.picks[2],
+            _ => self.picks[2] == self.picks[0],
+        };
+        Some(if won { 1.0 } else { 0.0 })
+    }
+}
+
+/// Guess a hidden number for a reward of 1
+#[derive(Clone, Debug, Default)]
+struct GuessGame {
+    secret: u8,
+    guess: Option<u8>,
+}
+
+impl Game for GuessGame {
+    type Move = u8;
+
+    type PlayerTag = usize;
+
+    type MoveList = Vec<u8>;
+
+    fn randomize_determination<R: Rng + ?Sized>(
+        &mut self,
+        _observer: Self::PlayerTag,
+        rng: &mut R,
+    ) {
+        self.secret = rng.gen_range(0, 4);
+    }
+
+    fn current_player(&self) -> Self::PlayerTag {
+        0
+    }
+
+    fn next_player(&self) -> Self::PlayerTag {
+        0
+    }
+
+    fn available_moves(&self) -> Self::MoveList {
+        if self.guess.is_some() {
+            Vec::new()
+        } else {
+            (0..4).collect()
+        }
+    }
+
+    fn make_move(&mut self, mov: &Self::Move) {
+        self.guess = Some(*mov);
+    }
+
+    fn result(&self, _player: Self::PlayerTag) -> Option<f64> {
+        self.guess.map(|g| if g == self.secret { 1.0 } else { 0.0 })
+    }
+}
+
+/// Kuhn poker: each player antes 1 and holds one of a jack (0), queen (1) or king (2), then
+/// they pass (`false`) or bet (`true`) 1 more in turn until someone folds or both have acted
+#[derive(Clone, Debug)]
+struct KuhnPoker {
+    cards: [u8; 2],
+    bets: Vec<bool>,
+}
+
+impl Default for KuhnPoker {
+    fn default() -> Self {
+        KuhnPoker {
+            cards: [2, 0],
+            bets: Vec::new(),
+        }
+    }
+}
+
+impl Game for KuhnPoker {
+    type Move = bool;
+
+    type PlayerTag = usize;
+
+    type MoveList = Vec<bool>;
+
+    fn randomize_determination<R: Rng + ?Sized>(&mut self, observer: Self::PlayerTag, rng: &mut R) {
+        let opponent = 1 - observer;
+        self.cards[opponent] = loop {
+            let card = rng.gen_range(0, 3);
+            if card != self.cards[observer] {
+                break card;
+            }
+        };
+    }
+
+    fn current_player(&self) -> Self::PlayerTag {
+        self.bets.len() % 2
+    }
+
+    fn next_player(&self) -> Self::PlayerTag {
+        (self.bets.len() + 1) % 2
+    }
+
+    fn available_moves(&self) -> Self::MoveList {
+        match self.bets.as_slice() {
+            [] | [false] | [true] | [false, true] => vec![false, true],
+            _ => Vec::new(),
+        }
+    }
+
+    fn make_move(&mut self, mov: &Self::Move) {
+        self.bets.push(*mov);
+    }
+
+    fn result(&self, player: Self::PlayerTag) -> Option<f64> {
+        let showdown = |stake: f64| {
+            if self.cards[player] > self.cards[1 - player] {
+                stake
+            } else {
+                -stake
+            }
+        };
+        match self.bets.as_slice() {
+            [false, false] => Some(showdown(1.0)),
+            [true, true] | [false, true, true] => Some(showdown(2.0)),
+            // The player who passed after a bet folds
+            [true, false] | [false, true, false] => {
+                let folded = (self.bets.len() - 1) % 2;
+                Some(if player == folded { -1.0 } else { 1.0 })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// The first player picks a number in secret and the second player wins by guessing it
+#[derive(Clone, Debug, Default)]
+struct HiddenPickGame {
+    pick: Option<u8>,
+    guess: Option<u8>,
+}
+
+impl Game for HiddenPickGame {
+    type Move = u8;
+
+    type PlayerTag = usize;
+
+    type MoveList = Vec<u8>;
+
+    fn randomize_determination<R: Rng + ?Sized>(&mut self, observer: Self::PlayerTag, rng: &mut R) {
+        if observer == 1 && self.pick.is_some() {
+            self.pick = Some(rng.gen_range(0, 4));
+        }
+    }
+
+    fn current_player(&self) -> Self::PlayerTag {
+        if self.pick.is_none() {
+            0
+        } else {
+            1
+        }
+    }
+
+    fn next_player(&self) -> Self::PlayerTag {
+        1 - self.current_player()
+    }
+
+    fn available_moves(&self) -> Self::MoveList {
+        if self.guess.is_some() {
+            Vec::new()
+        } else {
+            (0..4).collect()
+        }
+    }
+
+    fn make_move(&mut self, mov: &Self::Move) {
+        if self.pick.is_none() {
+            self.pick = Some(*mov);
+        } else {
+            self.guess = Some(*mov);
+        }
+    }
+
+    fn observe_move(&self, mov: &Self::Move, observer: Self::PlayerTag) -> Self::Move {
+        if observer == self.current_player() {
+            *mov
+        } else {
+            u8::MAX
+        }
+    }
+
+    fn result(&self, player: Self::PlayerTag) -> Option<f64> {
+        let correct = self.guess? == self.pick?;
+        Some(if correct == (player == 1) { 1.0 } else { 0.0 })
+    }
+}
+
+/// Rock paper scissors, where the second player chooses without seeing the first player's move
+#[derive(Clone, Debug, Default)]
+struct RockPaperScissors {
+    moves: Vec<u8>,
+}
+
+impl Game for RockPaperScissors {
+    type Move = u8;
+
+    type PlayerTag = usize;
+
+    type MoveList = Vec<u8>;
+
+    fn randomize_determination<R: Rng + ?Sized>(
+        &mut self,
+        _observer: Self::PlayerTag,
+        _rng: &mut R,
+    ) {
+    }
+
+    fn current_player(&self) -> Self::PlayerTag {
+        self.moves.len() % 2
+    }
+
+    fn next_player(&self) -> Self::PlayerTag {
+        (self.moves.len() + 1) % 2
+    }
+
+    fn is_simultaneous(&self) -> bool {
+        self.moves.len() == 1
+    }
+
+    fn available_moves(&self) -> Self::MoveList {
+        if self.moves.len() < 2 {
+            (0..3).collect()
+        } else {
+            Vec::new()
+        }
+    }
+
+    fn make_move(&mut self, mov: &Self::Move) {
+        self.moves.push(*mov);
+    }
+
+    fn result(&self, player: Self::PlayerTag) -> Option<f64> {
+        let (own, other) = match (self.moves.as_slice(), player) {
+            ([first, second], 0) => (*first, *second),
+            ([first, second], _) => (*second, *first),
+            _ => return None,
+        };
+        // Each move beats the one before it
+        Some(match (3 + own - other) % 3 {
+            0 => 0.5,
+            1 => 1.0,
+            _ => 0.0,
+        })
+    }
+}
+
+/// Call a number, then roll a die, winning if it comes up at least the number called
+#[derive(Clone, Debug, Default)]
+struct DieGame {
+    call: Option<u8>,
+    roll: Option<u8>,
+}
+
+impl Game for DieGame {
+    type Move = u8;
+
+    type PlayerTag = usize;
+
+    type MoveList = Vec<u8>;
+
+    fn randomize_determination<R: Rng + ?Sized>(
+        &mut self,
+        _observer: Self::PlayerTag,
+        _rng: &mut R,
+    ) {
+    }
+
+    fn current_player(&self) -> Self::PlayerTag {
+        0
+    }
+
+    fn next_player(&self) -> Self::PlayerTag {
+        0
+    }
+
+    fn is_chance_node(&self) -> bool {
+        self.call.is_some() && self.roll.is_none()
+    }
+
+    fn sample_chance<R: Rng + ?Sized>(&mut self, rng: &mut R) {
+        self.roll = Some(rng.gen_range(1, 7));
+    }
+
+    fn available_moves(&self) -> Self::MoveList {
+        if self.call.is_none() {
+            (1..=6).collect()
+        } else {
+            Vec::new()
+        }
+    }
+
+    fn make_move(&mut self, mov: &Self::Move) {
+        self.call = Some(*mov);
+    }
+
+    fn result(&self, _player: Self::PlayerTag) -> Option<f64> {
+        Some(if self.roll? >= self.call? { 1.0 } else { 0.0 })
+    }
+}
+
+/// Each player in turn picks 0, 1 or 2, and the higher pick wins. Equal picks draw.
+#[derive(Clone, Debug, Default)]
+struct PickHigherGame {
+    picks: Vec<u8>,
+}
+
+impl Game for PickHigherGame {
+    type Move = u8;
+
+    type PlayerTag = usize;
+
+    type MoveList = Vec<u8>;
+
+    fn randomize_determination<R: Rng + ?Sized>(
+        &mut self,
+        _observer: Self::PlayerTag,
+        _rng: &mut R,
+    ) {
+    }
+
+    fn current_player(&self) -> Self::PlayerTag {
+        self.picks.len() % 2
+    }
+
+    fn next_player(&self) -> Self::PlayerTag {
+        1 - self.current_player()
+    }
+
+    fn available_moves(&self) -> Self::MoveList {
+        if self.picks.len() < 2 {
+            vec![0, 1, 2]
+        } else {
+            Vec::new()
+        }
+    }
+
+    fn make_move(&mut self, mov: &Self::Move) {
+        self.picks.push(*mov);
+    }
+
+    fn outcome(&self, player: Self::PlayerTag) -> Option<Outcome> {
+        match self.picks.as_slice() {
+            [first, second] => Some(match (first.cmp(second), player) {
+                (std::cmp::Ordering::Equal, _) => Outcome::Draw,
+                (std::cmp::Ordering::Greater, 0) | (std::cmp::Ordering::Less, 1) => Outcome::Win,
+                _ => Outcome::Loss,
+            }),
+            _ => None,
+        }
+    }
+
+    fn result(&self, player: Self::PlayerTag) -> Option<f64> {
+        self.outcome(player).map(Outcome::reward)
+    }
+}
+
+/// The first of three players picks one of `PAYOFFS`, which ends the game with those rewards
+#[derive(Clone, Debug, Default)]
+struct PayoffPickGame {
+    pick: Option<usize>,
+}
+
+impl PayoffPickGame {
+    const PAYOFFS: [[f64; 3]; 2] = [[1.0, 2.0, 3.0], [0.5, 0.0, 4.0]];
+}
+
+impl Game for PayoffPickGame {
+    type Move = usize;
+
+    type PlayerTag = usize;
+
+    type MoveList = Vec<usize>;
+
+    fn randomize_determination<R: Rng + ?Sized>(
+        &mut self,
+        _observer: Self::PlayerTag,
+        _rng: &mut R,
+    ) {
+    }
+
+    fn current_player(&self) -> Self::PlayerTag {
+        0
+    }
+
+    fn next_player(&self) -> Self::PlayerTag {
+        1
+    }
+
+    fn available_moves(&self) -> Self::MoveList {
+        match self.pick {
+            None => vec![0, 1],
+            Some(_) => Vec::new(),
+        }
+    }
+
+    fn make_move(&mut self, mov: &Self::Move) {
+        self.pick = Some(*mov);
+    }
+
+    fn result(&self, player: Self::PlayerTag) -> Option<f64> {
+        Some(Self::PAYOFFS[self.pick?][player])
+    }
+}
+
+const ITERATIONS: usize = 1000;
+
+#[test]
+pub fn number_of_children_1_thread() {
+    number_of_children(1);
+}
+
+#[test]
+pub fn number_of_children_4_threads() {
+    number_of_children(4);
+}
+
+fn number_of_children(n_threads: usize) {
+    let game = TenMoveGame::default();
+    let ismcts = IsmctsHandler::new(game);
+    ismcts.run_iterations(n_threads, ITERATIONS);
+    // ismcts.debug_children();
+
+    let children = ismcts.root_node.children.read().unwrap();
+
+    let total_iterations = ITERATIONS * n_threads;
+    assert_eq!(10, children.len());
+    assert_eq!(
+        total_iterations,
+        children
+            .iter()
+            .map(|c| c.statistics.snapshot().visit_count)
+            .sum::<usize>()
+    );
+
+    for (index, child) in children.iter().enumerate() {
+        // Every iteration chooses a move at the root, so each child was available in every
+        // iteration from the one which added it on. With more threads, iterations racing with
+        // the one adding a child may not see it, so there's no exact count to check.
+        if n_threads == 1 {
+            assert_eq!(
+                total_iterations - index,
+                child.statistics.snapshot().availability_count
+            );
+        }
+        assert_eq!(10, child.children.read().unwrap().len());
+    }
+}
+
+#[test]
+pub fn custom_backprop_aggregation_sets_value() {
+    let game = TenMoveGame::default();
+    let mut ismcts = IsmctsHandler::new(game);
+    ismcts.config_mut().backprop = BackpropAggregation::Custom(Arc::new(|_| 0.5));
+    ismcts.run_iterations(2, 200);
+
+    let children = ismcts.root_node.children.read().unwrap();
+    for child in children.iter() {
+        assert_eq!(0.5, child.statistics.snapshot().mean());
+    }
+}
+
+#[test]
+pub fn max_backprop_finds_minimax_values() {
+    let mut ismcts = IsmctsHandler::with_seed(Nim::new(vec![2, 3]), 0);
+    ismcts.config_mut().backprop = BackpropAggregation::Max;
+    ismcts.run_iterations(1, 5000);
+
+    // The first player wins exactly by leaving heaps with a nim-sum of zero
+    let children = ismcts.root_node.children.read().unwrap();
+    assert_eq!(5, children.len());
+    for child in children.iter() {
+        let mov = child.mov.unwrap();
+        let mut heaps = [2, 3];
+        heaps[mov.heap] -= mov.amount;
+        let expected = if heaps[0] ^ heaps[1] == 0 { 1.0 } else { 0.0 };
+        assert_eq!(expected, child.statistics.snapshot().mean(), "{:?}", mov);
+    }
+}
+
+#[test]
+pub fn sampled_rollouts_reach_terminal() {
+    let game = TenMoveGame::default();
+    let mut ismcts = IsmctsHandler::new(game);
+    ismcts.config_mut().rollout_move_sample_size = Some(3);
+    ismcts.run_iterations(2, ITERATIONS);
+
+    assert_eq!(2 * ITERATIONS, ismcts.total_visits());
+
+    // Sampling no moves samples all of them, rather than ending the rollout
+    for sample_size in [Some(0), Some(1)] {
+        let config = SearchConfig {
+            rollout_move_sample_size: sample_size,
+            ..Default::default()
+        };
+        let mut state = TenMoveGame::default();
+        rollout(
+            &mut state,
+            &config,
+            None,
+            &mut StdRng::seed_from_u64(0),
+            None,
+        );
+        assert!(state.result(0).is_some(), "{:?}", sample_size);
+    }
+}
+
+#[test]
+pub fn sampled_rollout_moves_follow_move_priority() {
+    let game = Instrumented {
+        priority_move: Some(7),
+        ..Instrumented::<TenMoveGame>::default()
+    };
+    let rollout_moves = |sample_size| {
+        let config = SearchConfig {
+            rollout_move_sample_size: sample_size,
+            ..Default::default()
+        };
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut moves = Vec::new();
+        for _ in 0..100 {
+            let mut history = Vec::new();
+            rollout(
+                &mut game.clone(),
+                &config,
+                None,
+                &mut rng,
+                Some(&mut history),
+            );
+            moves.extend(history.into_iter().map(|(_, m)| m));
+        }
+        moves
+    };
+    let prioritized =
+        |moves: Vec<u8>| moves.iter().filter(|&&m| m == 7).count() as f64 / moves.len() as f64;
+    // Sampling every move always finds the prioritized one
+    assert_eq!(1.0, prioritized(rollout_moves(Some(10))));
+    // Of two sampled moves, it's played whenever sampled, a fifth of the time, while uniformly
+    // random moves play it a tenth of the time
+    let sampled = prioritized(rollout_moves(Some(2)));
+    assert!(sampled > 0.15, "{}", sampled);
+    let uniform = prioritized(rollout_moves(None));
+    assert!(uniform < 0.15, "{}", uniform);
+}
+
+#[test]
+pub fn warm_start_copies_matching_subtree() {
+    let prior = IsmctsHandler::new(TenMoveGame::default());
+    prior.run_iterations(1, ITERATIONS);
+
+    let mut ismcts = IsmctsHandler::new(TenMoveGame { moves: vec![3] });
+    assert!(ismcts.warm_start_from(&prior));
+
+    let prior_children = prior.root_node.children.read().unwrap();
+    let matching = prior_children.iter().find(|c| c.mov == Some(3)).unwrap();
+    assert_eq!(
+        matching.statistics.snapshot().visit_count,
+        ismcts.root_node.statistics.snapshot().visit_count
+    );
+    assert_eq!(
+        matching.children.read().unwrap().len(),
+        ismcts.root_node.children.read().unwrap().len()
+    );
+    assert!(ismcts.root_node.parent.read().unwrap().is_none());
+
+    let mut unrelated = IsmctsHandler::new(TenMoveGame {
+        moves: vec![1, 2, 3, 4],
+    });
+    assert!(!unrelated.warm_start_from(&prior));
+}
+
+/// Add a root child with the given statistics to a tree of `TenMoveGame`
+fn add_visited_child(
+    ismcts: &IsmctsHandler<TenMoveGame>,
+    mov: u8,
+    visit_count: usize,
+    reward: f64,
+) -> Arc<Node<TenMoveGame>> {
+    let child = Arc::clone(&ismcts.root_node).add_child(
+        mov,
+        0,
+        1.0,
+        Default::default(),
+        &ismcts.node_count,
+    );
+    child.statistics.store(&NodeStatistics {
+        visit_count,
+        availability_count: visit_count,
+        reward,
+        ..Default::default()
+    });
+    child
+}
+
+/// Always selects the child with the highest mean reward
+#[derive(Debug)]
+struct Greedy;
+
+impl SelectionPolicy for Greedy {
+    fn score(&self, statistics: &NodeStatistics, _availability: usize) -> f64 {
+        statistics.mean()
+    }
+}
+
+#[test]
+pub fn custom_selection_policy_replaces_ucb1() {
+    let select = |ismcts: &IsmctsHandler<TenMoveGame>| {
+        let best = add_visited_child(ismcts, 0, 100, 60.0);
+        let rare = add_visited_child(ismcts, 1, 1, 0.5);
+        rare.statistics
+            .availability_count
+            .store(100, Ordering::Relaxed);
+        let run = ismcts.search_run(1);
+        let chosen = ismcts
+            .root_node
+            .select_child(&[0, 1], &run, None, None)
+            .unwrap();
+        if Arc::ptr_eq(&chosen, &best) {
+            0
+        } else {
+            1
+        }
+    };
+    // UCB1 explores the rarely visited child
+    assert_eq!(1, select(&IsmctsHandler::new(TenMoveGame::default())));
+    let greedy = IsmctsBuilder::new()
+        .selection_policy(Greedy)
+        .build(TenMoveGame::default());
+    assert_eq!(0, select(&greedy));
+}
+
+/// Greedy, recording the priors it is given
+#[derive(Debug, Default)]
+struct PriorRecording(Arc<Mutex<Vec<f64>>>);
+
+impl SelectionPolicy for PriorRecording {
+    fn score(&self, statistics: &NodeStatistics, _availability: usize) -> f64 {
+        statistics.mean()
+    }
+
+    fn score_with_virtual_loss(
+        &self,
+        statistics: &NodeStatistics,
+        availability: usize,
+        _virtual_loss: f64,
+        prior: f64,
+    ) -> f64 {
+        self.0.lock().unwrap().push(prior);
+        self.score(statistics, availability)
+    }
+}
+
+#[test]
+pub fn custom_selection_policy_gets_priors() {
+    let priors = Arc::new(Mutex::new(Vec::new()));
+    let ismcts = IsmctsBuilder::new()
+        .selection_policy(PriorRecording(Arc::clone(&priors)))
+        .build(TenMoveGame::default());
+    ismcts.run_iterations(1, 100);
+    let priors = priors.lock().unwrap();
+    assert!(!priors.is_empty());
+    assert!(priors.iter().all(|&p| p == 0.1), "{:?}", priors);
+
+    // Simultaneous rounds choose from the priors too
+    let priors = Arc::new(Mutex::new(Vec::new()));
+    let ismcts = IsmctsBuilder::new()
+        .selection_policy(PriorRecording(Arc::clone(&priors)))
+        .build(RockPaperScissors::default());
+    ismcts.run_iterations(1, 100);
+    let priors = priors.lock().unwrap();
+    assert!(!priors.is_empty());
+    assert!(
+        priors.iter().all(|&p| (p - 1.0 / 3.0).abs() < 1e-12),
+        "{:?}",
+        priors
+    );
+}
+
+#[test]
+pub fn visit_value_disagreement_detected() {
+    let ismcts = IsmctsHandler::new(TenMoveGame::default());
+    assert_eq!(None, ismcts.visit_value_disagreement());
+
+    let root = Arc::clone(&ismcts.root_node);
+    let frequent = Arc::clone(&root).add_child(1, 0, 1.0, Default::default(), &ismcts.node_count);
+    let valuable = Arc::clone(&root).add_child(2, 0, 1.0, Default::default(), &ismcts.node_count);
+    let unvisited = root.add_child(3, 0, 1.0, Default::default(), &ismcts.node_count);
+    frequent.statistics.store(&NodeStatistics {
+        visit_count: 10,
+        reward: 5.0,
+        ..Default::default()
+    });
+    valuable.statistics.store(&NodeStatistics {
+        visit_count: 2,
+        reward: 2.0,
+        ..Default::default()
+    });
+    unvisited.statistics.reward.store(100.0);
+
+    assert_eq!(Some((1, 2)), ismcts.visit_value_disagreement());
+
+    valuable.statistics.reward.store(0.0);
+    assert_eq!(None, ismcts.visit_value_disagreement());
+}
+
+#[test]
+pub fn visit_value_disagreement_breaks_ties_like_best_move() {
+    // Expanded in the opposite order to `available_moves`
+    let ismcts = IsmctsHandler::new(TenMoveGame::default());
+    let later = add_visited_child(&ismcts, 2, 5, 4.0);
+    add_visited_child(&ismcts, 1, 5, 1.0);
+    assert_eq!(Some(1), ismcts.best_move());
+    assert_eq!(Some((1, 2)), ismcts.visit_value_disagreement());
+    assert_eq!(None, ismcts.best_move_by(BestMoveCriterion::RobustMax));
+
+    // Tied on both counts, every ranking picks the move listed first
+    later.statistics.reward.store(1.0);
+    assert_eq!(None, ismcts.visit_value_disagreement());
+    assert_eq!(Some(1), ismcts.best_move_by(BestMoveCriterion::RobustMax));
+    assert_eq!(
+        Some(1),
+        ismcts.best_move_by(BestMoveCriterion::HighestReward { min_visits: 0 })
+    );
+}
+
+#[test]
+pub fn num_available_moves_default_counts_list() {
+    let game = TenMoveGame::default();
+    assert_eq!(game.available_moves().len(), game.num_available_moves());
+    let finished = TenMoveGame {
+        moves: vec![0, 1, 2],
+    };
+    assert_eq!(0, finished.num_available_moves());
+}
+
+#[test]
+pub fn replay_reports_each_step() {
+    let mut ismcts = IsmctsHandler::new(TenMoveGame::default());
+    let reports = ismcts.replay(&[0, 1], 1, 200);
+
+    assert_eq!(2, reports.len());
+    assert_eq!(vec![0, 1], ismcts.state().moves);
+    for (report, logged) in reports.iter().zip(&[0, 1]) {
+        assert_eq!(*logged, report.logged_move);
+        assert!(report.engine_move.is_some());
+        assert!(report.engine_value.is_some());
+        assert_eq!(report.agreed, report.engine_move == Some(*logged));
+    }
+}
+
+#[test]
+pub fn parent_availability_finds_winning_move() {
+    // The only winning move leaves two equal heaps
+    let winning = NimMove { heap: 1, amount: 1 };
+    for availability in [UcbAvailability::Child, UcbAvailability::Parent] {
+        let mut ismcts = IsmctsHandler::new(Nim::new(vec![1, 2]));
+        ismcts.config_mut().ucb_availability = availability;
+        ismcts.run_iterations(1, 2000);
+        assert_eq!(Some(winning), ismcts.best_move(), "{:?}", availability);
+    }
+}
+
+#[test]
+pub fn max_rollout_depth_evaluates_early() {
+    let config = SearchConfig {
+        max_rollout_depth: Some(3),
+        ..Default::default()
+    };
+    let mut state = EndlessGame::default();
+    rollout(
+        &mut state,
+        &config,
+        None,
+        &mut StdRng::seed_from_u64(0),
+        None,
+    );
+    assert_eq!(3, state.turn);
+
+    let mut ismcts = IsmctsHandler::new(EndlessGame::default());
+    *ismcts.config_mut() = config;
+    ismcts.run_iterations(2, 5);
+    assert_eq!(10, ismcts.total_visits());
+    for child in ismcts.root_node.children.read().unwrap().iter() {
+        assert_eq!(0.5, child.statistics.snapshot().mean());
+    }
+}
+
+#[test]
+pub fn iteration_timeout_stops_endless_rollouts() {
+    let mut ismcts = IsmctsHandler::new(EndlessGame::default());
+    ismcts.config_mut().iteration_timeout = Some(Duration::from_millis(5));
+    ismcts.run_iterations(2, 5);
+
+    let children = ismcts.root_node.children.read().unwrap();
+    let visits: usize = children
+        .iter()
+        .map(|c| c.statistics.snapshot().visit_count)
+        .sum();
+    assert_eq!(10, visits);
+    for child in children.iter() {
+        assert_eq!(0.5, child.statistics.snapshot().mean());
+    }
+}
+
+#[test]
+pub fn rollouts_stop_at_move_cap() {
+    let mut state = EndlessGame::default();
+    state.random_rollout(&mut StdRng::seed_from_u64(0), DEFAULT_MAX_ROLLOUT_MOVES);
+    assert_eq!(DEFAULT_MAX_ROLLOUT_MOVES, state.turn);
+
+    let config = SearchConfig {
+        max_rollout_moves: 50,
+        ..Default::default()
+    };
+    for chance in [false, true] {
+        for history in [None, Some(&mut Vec::new())] {
+            let mut state = EndlessGame {
+                chance,
+                ..Default::default()
+            };
+            rollout(
+                &mut state,
+                &config,
+                None,
+                &mut StdRng::seed_from_u64(0),
+                history,
+            );
+            assert_eq!(50, state.turn, "chance {}", chance);
+        }
+    }
+
+    // Without any other limits the search still finishes
+    let ismcts = IsmctsHandler::new(EndlessGame::default());
+    ismcts.run_iterations(2, 5);
+    assert_eq!(10, ismcts.total_visits());
+}
+
+#[test]
+pub fn managed_time_grows_with_branching() {
+    let remaining = Duration::from_secs(100);
+    let even_share = Duration::from_secs(10);
+    let times: Vec<_> = [1, 4, 16, 64, 1000]
+        .iter()
+        .map(|&branching| managed_time(remaining, 10, branching))
+        .collect();
+    assert!(times.windows(2).all(|w| w[0] <= w[1]), "{:?}", times);
+    assert!(times[1] < times[3]);
+    assert_eq!(even_share, times[2]);
+    assert_eq!(even_share / 2, times[0]);
+    assert_eq!(even_share * 3, times[4]);
+    // Never more than half of what is left
+    assert_eq!(remaining / 2, managed_time(remaining, 1, 1000));
+    assert_eq!(remaining / 2, managed_time(remaining, 0, 16));
+
+    let ismcts = IsmctsHandler::new(TenMoveGame::default());
+    let used = ismcts.run_managed(1, Duration::from_millis(400), 10, 16);
+    assert!(used >= Duration::from_millis(40), "{:?}", used);
+    assert!(used < Duration::from_millis(200), "{:?}", used);
+    assert!(ismcts.total_visits() > 0);
+}
+
+#[test]
+pub fn stream_snapshots_during_search() {
+    let ismcts = IsmctsHandler::new(TenMoveGame::default());
+    let stop = AtomicBool::new(false);
+    let mut snapshots = Vec::new();
+    std::thread::scope(|s| {
+        s.spawn(|| {
+            ismcts.run_timed(2, Duration::from_millis(50));
+            stop.store(true, Ordering::SeqCst);
+        });
+        ismcts.stream_tree_snapshots(Duration::from_millis(5), 1, &stop, |snapshot| {
+            snapshots.push(snapshot)
+        });
+    });
+
+    assert!(snapshots.len() >= 2);
+    let last = snapshots.last().unwrap();
+    assert_eq!(11, last.nodes.len());
+    assert_eq!(ismcts.total_visits(), last.nodes[0].visit_count);
+    assert!(last.nodes[1..].iter().all(|n| n.parent == Some(0)));
+    assert_eq!(11, last.to_dot().matches("label=").count());
+    assert!(last.to_json().starts_with("{\"nodes\":[{\"parent\":null"));
+}
+
+#[test]
+pub fn best_move_snapshot_during_search() {
+    let ismcts = IsmctsHandler::new(TenMoveGame::default());
+    assert_eq!(None, ismcts.best_move_snapshot());
+
+    let searching = AtomicBool::new(true);
+    let mut snapshots = Vec::new();
+    std::thread::scope(|s| {
+        s.spawn(|| {
+            ismcts.run_timed(2, Duration::from_millis(50));
+            searching.store(false, Ordering::SeqCst);
+        });
+        while searching.load(Ordering::SeqCst) {
+            snapshots.extend(ismcts.best_move_snapshot());
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    });
+
+    assert!(!snapshots.is_empty());
+    for snapshot in &snapshots {
+        assert!(snapshot.visits <= snapshot.total_visits);
+        assert!(snapshot.visits * 10 >= snapshot.total_visits);
+    }
+    let last = ismcts.best_move_snapshot().unwrap();
+    assert_eq!(ismcts.best_move(), Some(last.mov));
+    assert_eq!(ismcts.total_visits(), last.total_visits);
+}
+
+#[test]
+pub fn best_move_confidence() {
+    let ismcts = IsmctsHandler::new(TenMoveGame::default());
+    assert_eq!(None, ismcts.best_move_confidence_probability());
+
+    let root = Arc::clone(&ismcts.root_node);
+    let best = Arc::clone(&root).add_child(1, 0, 1.0, Default::default(), &ismcts.node_count);
+    let runner_up = root.add_child(2, 0, 1.0, Default::default(), &ismcts.node_count);
+    best.statistics.store(&NodeStatistics {
+        visit_count: 100,
+        reward: 70.0,
+        reward_sq: 70.0,
+        ..Default::default()
+    });
+    assert_eq!(None, ismcts.best_move_confidence_probability());
+
+    runner_up.statistics.store(&NodeStatistics {
+        visit_count: 50,
+        reward: 25.0,
+        reward_sq: 25.0,
+        ..Default::default()
+    });
+    // Difference of 0.2 with a standard error of sqrt(0.21 / 100 + 0.25 / 50)
+    let p = ismcts.best_move_confidence_probability().unwrap();
+    assert!((p - 0.99122).abs() < 1e-3, "{}", p);
+
+    runner_up.statistics.reward.store(40.0);
+    runner_up.statistics.reward_sq.store(40.0);
+    let p = ismcts.best_move_confidence_probability().unwrap();
+    assert!(p > 0.0 && p < 0.5, "{}", p);
+
+    // The value kept by a non-additive aggregation counts, not the mean of the rewards
+    best.statistics.store(&NodeStatistics {
+        visit_count: 100,
+        reward: 70.0,
+        reward_sq: 70.0,
+        value: Some(0.95),
+        ..Default::default()
+    });
+    let p = ismcts.best_move_confidence_probability().unwrap();
+    assert!(p > 0.5, "{}", p);
+}
+
+#[test]
+pub fn depth_histogram_of_full_tree() {
+    let ismcts = IsmctsHandler::new(TenMoveGame::default());
+    assert_eq!(vec![1], ismcts.depth_histogram());
+
+    ismcts.run_iterations(1, ITERATIONS);
+    let histogram = ismcts.depth_histogram();
+    assert_eq!(&[1, 10, 100], &histogram[..3]);
+    assert_eq!(
+        histogram.iter().sum::<usize>(),
+        ismcts.tree_snapshot(usize::MAX).nodes.len()
+    );
+}
+
+#[test]
+pub fn zero_sum_searches_for_both_players() {
+    // The only winning move leaves two equal heaps
+    let winning = NimMove { heap: 1, amount: 1 };
+    for player in [0, 1] {
+        let mut nim = Instrumented {
+            result_for: Some(0),
+            ..Instrumented::new(Nim::new(vec![1, 2]))
+        };
+        nim.game.player_to_move = player;
+        let mut ismcts = IsmctsHandler::new_zero_sum(nim, 0);
+        ismcts.config_mut().seed = Some(0);
+        ismcts.run_iterations(1, 2000);
+        assert_eq!(Some(winning), ismcts.best_move(), "player {}", player);
+        let best = &ismcts.ranked_moves()[0];
+        assert!(best.mean_reward.unwrap() > 0.9, "{:?}", best);
+    }
+}
+
+#[test]
+pub fn zero_sum_results_for_both_players() {
+    // Player 0 takes the last stick and wins
+    let nim = Instrumented {
+        result_for: Some(0),
+        ..Instrumented::new(Nim::new(vec![1]))
+    };
+    let mut ismcts = IsmctsHandler::new_zero_sum(nim, 0);
+    ismcts.config_mut().record_result_distribution = true;
+    let mut target = ismcts.record_policy_target();
+    ismcts.make_move_unchecked(&NimMove { heap: 0, amount: 1 });
+    ismcts.run_iterations(1, 10);
+    // Player 1 searches the finished game and has lost every simulation
+    assert_eq!(vec![(0.0, 1.0)], ismcts.result_distribution());
+
+    target.player = 1;
+    target.attach_result(ismcts.state(), ismcts.config());
+    assert_eq!(Some(0.0), target.result);
+
+    ismcts.config_mut().reward_bounds = Some((-1.0, 1.0));
+    target.attach_result(ismcts.state(), ismcts.config());
+    assert_eq!(Some(-1.0), target.result);
+}
+
+#[test]
+pub fn unexplored_move_reroots_at_transposition() {
+    let take_one = NimMove { heap: 0, amount: 1 };
+    let mut ismcts = IsmctsHandler::new(Nim::new(vec![3, 1]));
+    let mut node = Arc::clone(&ismcts.root_node);
+    node.statistics.visit_count.store(7, Ordering::Relaxed);
+    for player in [0, 1, 0] {
+        node = node.add_child(
+            take_one,
+            player,
+            1.0,
+            Default::default(),
+            &ismcts.node_count,
+        );
+        node.statistics.visit_count.store(7, Ordering::Relaxed);
+    }
+    node.statistics.leaf_visits.store(7, Ordering::Relaxed);
+
+    // Taking all three at once reaches the same position as taking one three times
+    assert!(ismcts.visits_are_consistent());
+    assert_eq!(Ok(()), ismcts.make_move(&NimMove { heap: 0, amount: 3 }));
+    assert!(Arc::ptr_eq(&node, &ismcts.root_node));
+
+    // No match anywhere, so a fresh root is used
+    assert_eq!(
+        Err(MoveError::Unexplored),
+        ismcts.make_move(&NimMove { heap: 1, amount: 1 })
+    );
+    assert_eq!(0, ismcts.root_node.statistics.snapshot().visit_count);
+    assert!(ismcts.root_node.children.read().unwrap().is_empty());
+}
+
+#[test]
+pub fn root_noise_varies_search() {
+    let search = |noise_seeds: &[u64]| {
+        let mut ismcts = IsmctsHandler::with_seed(Nim::new(vec![1, 2]), 7);
+        // Also mixed into the priors of children expanded before the noise
+        ismcts.run_iterations(1, 10);
+        for &noise_seed in noise_seeds {
+            ismcts.add_root_noise(0.3, 0.25, &mut StdRng::seed_from_u64(noise_seed));
+        }
+        ismcts.run_iterations(1, ITERATIONS);
+        assert_eq!(Some(NimMove { heap: 1, amount: 1 }), ismcts.best_move());
+        // The children keep their own priors, which the noise is mixed with
+        for child in ismcts.root_node.children.read().unwrap().iter() {
+            assert_eq!(1.0, child.prior.load());
+        }
+        ismcts.move_statistics()
+    };
+    assert_eq!(search(&[1]), search(&[1]));
+    assert_ne!(search(&[1]), search(&[2]));
+    // Adding noise again replaces it
+    assert_eq!(search(&[1]), search(&[2, 1]));
+}
+
+#[test]
+pub fn principal_variation_follows_optimal_line() {
+    let ismcts = IsmctsHandler::new(Nim::new(vec![1, 2]));
+    ismcts.run_iterations(1, 5 * ITERATIONS);
+    let line = ismcts.principal_variation(10);
+    assert_eq!(3, line.len());
+    assert_eq!(NimMove { heap: 1, amount: 1 }, line[0]);
+
+    let mut state = ismcts.state().clone();
+    for mov in &line {
+        state.make_move(mov);
+    }
+    assert_eq!(Some(1.0), state.result(0));
+    assert_eq!(line[..1], ismcts.principal_variation(1)[..]);
+}
+
+#[test]
+pub fn best_move_checked_explains_missing_move() {
+    let ismcts = IsmctsHandler::new(Nim::new(vec![1, 2]));
+    assert_eq!(Err(NoMoveReason::Unexplored), ismcts.best_move_checked());
+    ismcts.run_iterations(1, ITERATIONS);
+    assert_eq!(
+        Ok(NimMove { heap: 1, amount: 1 }),
+        ismcts.best_move_checked()
+    );
+
+    let terminal = IsmctsHandler::new(Nim::new(vec![0, 0]));
+    terminal.run_iterations(1, 10);
+    assert_eq!(Err(NoMoveReason::Terminal), terminal.best_move_checked());
+    assert_eq!(None, terminal.best_move());
+}
+
+#[test]
+pub fn make_move_reports_illegal_and_unexplored_moves() {
+    let mut ismcts = IsmctsHandler::new(Nim::new(vec![3, 4, 5]));
+    assert_eq!(
+        Err(MoveError::Illegal),
+        ismcts.make_move(&NimMove { heap: 0, amount: 4 })
+    );
+    assert_eq!(vec![3, 4, 5], ismcts.state().heaps);
+
+    assert_eq!(
+        Err(MoveError::Unexplored),
+        ismcts.make_move(&NimMove { heap: 2, amount: 5 })
+    );
+    assert_eq!(vec![3, 4, 0], ismcts.state().heaps);
+    // Search continues from the new position
+    ismcts.run_iterations(1, ITERATIONS);
+    assert_eq!(ITERATIONS, ismcts.total_visits());
+    assert!(ismcts.best_move().is_some_and(|m| m.heap < 2));
+}
+
+#[test]
+pub fn learning_rate_tracks_recent_rewards() {
+    let loss = TenMoveGame { moves: vec![0, 1] };
+    let win = TenMoveGame { moves: vec![1, 0] };
+    let ismcts = IsmctsHandler::new(TenMoveGame::default());
+    let node =
+        Arc::clone(&ismcts.root_node).add_child(0, 0, 1.0, Default::default(), &ismcts.node_count);
+    let average_node =
+        Arc::clone(&ismcts.root_node).add_child(1, 0, 1.0, Default::default(), &ismcts.node_count);
+
+    let config = SearchConfig {
+        learning_rate: Some(0.5),
+        ..Default::default()
+    };
+    let average_config = SearchConfig::default();
+    for state in std::iter::repeat_n(&loss, 50).chain(std::iter::repeat_n(&win, 10)) {
+        node.update(std::slice::from_ref(state), &config, 0, Access::Shared);
+        average_node.update(
+            std::slice::from_ref(state),
+            &average_config,
+            0,
+            Access::Shared,
+        );
+    }
+
+    let recent_mean = node.statistics.snapshot().mean();
+    assert!((recent_mean - (1.0 - 0.5f64.powi(10))).abs() < 1e-9);
+    assert!((average_node.statistics.snapshot().mean() - 10.0 / 60.0).abs() < 1e-9);
+}
+
+#[test]
+pub fn concurrent_learning_rate_updates_stay_in_bounds() {
+    // Every update is a win, so the mean may never leave 1
+    let win = TenMoveGame { moves: vec![1, 0] };
+    let config = SearchConfig {
+        learning_rate: Some(0.1),
+        ..Default::default()
+    };
+    let ismcts = IsmctsHandler::new(TenMoveGame::default());
+    for mov in 0..50 {
+        let node = Arc::clone(&ismcts.root_node).add_child(
+            mov,
+            0,
+            1.0,
+            Default::default(),
+            &ismcts.node_count,
+        );
+        std::thread::scope(|s| {
+            for _ in 0..8 {
+                let (node, config, win) = (&node, &config, &win);
+                s.spawn(move || {
+                    for _ in 0..200 {
+                        node.update(std::slice::from_ref(win), config, 0, Access::Shared);
+                    }
+                });
+            }
+        });
+        let statistics = node.statistics.snapshot();
+        assert_eq!(8 * 200, statistics.visit_count);
+        assert!((statistics.mean() - 1.0).abs() < 1e-9, "{:?}", statistics);
+    }
+}
+
+#[test]
+pub fn concurrent_updates_count_every_reward() {
+    // Every update is a win, so a snapshot may never count more visits than rewards
+    let win = TenMoveGame { moves: vec![1, 0] };
+    let config = SearchConfig::default();
+    let ismcts = IsmctsHandler::new(TenMoveGame::default());
+    let node =
+        Arc::clone(&ismcts.root_node).add_child(0, 0, 1.0, Default::default(), &ismcts.node_count);
+    let done = AtomicBool::new(false);
+    std::thread::scope(|s| {
+        let updaters: Vec<_> = (0..8)
+            .map(|_| {
+                s.spawn(|| {
+                    for _ in 0..10_000 {
+                        node.update(std::slice::from_ref(&win), &config, 0, Access::Shared);
+                    }
+                })
+            })
+            .collect();
+        s.spawn(|| {
+            while !done.load(Ordering::Relaxed) {
+                let statistics = node.statistics.snapshot();
+                assert!(statistics.reward >= statistics.visit_count as f64);
+            }
+        });
+        for updater in updaters {
+            updater.join().unwrap();
+        }
+        done.store(true, Ordering::Relaxed);
+    });
+    let statistics = node.statistics.snapshot();
+    assert_eq!(8 * 10_000, statistics.visit_count);
+    assert_eq!(8.0 * 10_000.0, statistics.reward);
+    assert_eq!(8 * 10_000, statistics.leaf_visits);
+}
+
+#[test]
+pub fn top_two_moves() {
+    let ismcts = IsmctsHandler::new(TenMoveGame::default());
+    let root = Arc::clone(&ismcts.root_node);
+    let best = Arc::clone(&root).add_child(4, 0, 1.0, Default::default(), &ismcts.node_count);
+    best.statistics.store(&NodeStatistics {
+        visit_count: 8,
+        reward: 6.0,
+        ..Default::default()
+    });
+    assert_eq!(None, ismcts.top_two());
+
+    root.add_child(5, 0, 1.0, Default::default(), &ismcts.node_count);
+    let (first, second) = ismcts.top_two().unwrap();
+    assert_eq!(
+        MoveStat {
+            mov: 4,
+            visits: 8,
+            mean_reward: Some(0.75)
+        },
+        first
+    );
+    assert_eq!(
+        MoveStat {
+            mov: 5,
+            visits: 0,
+            mean_reward: None
+        },
+        second
+    );
+}
+
+#[test]
+pub fn reused_state_buffers_avoid_clones() {
+    let game = Instrumented::<TenMoveGame>::default();
+    let counts = Arc::clone(&game.counts);
+    let mut ismcts = IsmctsHandler::new(game);
+    counts.clones.store(0, Ordering::SeqCst);
+    ismcts.run_iterations(1, ITERATIONS);
+    // One clone for the worker thread, then one per iteration
+    assert_eq!(1 + ITERATIONS, counts.clones.load(Ordering::SeqCst));
+
+    ismcts.config_mut().reuse_state_buffers = true;
+    counts.clones.store(0, Ordering::SeqCst);
+    ismcts.run_iterations(1, ITERATIONS);
+    assert_eq!(2, counts.clones.load(Ordering::SeqCst));
+    assert_eq!(2 * ITERATIONS, ismcts.total_visits());
+}
+
+#[test]
+pub fn unmade_moves_avoid_clones() {
+    let clones = |unmake, n_threads| {
+        let ismcts = IsmctsHandler::new(Instrumented::new(LargeStateGame::new(unmake)));
+        let counts = Arc::clone(&ismcts.state().counts);
+        counts.clones.store(0, Ordering::SeqCst);
+        ismcts.run_iterations(n_threads, ITERATIONS);
+        assert_eq!(n_threads * ITERATIONS, ismcts.total_visits());
+        assert!(ismcts.state().game.board.iter().all(|&cell| cell == 0));
+        counts.clones.load(Ordering::SeqCst)
+    };
+    // One clone for each worker thread, then one per iteration unless moves are unmade
+    assert_eq!(1 + ITERATIONS, clones(false, 1));
+    assert_eq!(2, clones(true, 1));
+    assert_eq!(4 * 2, clones(true, 4));
+
+    let mut ismcts = IsmctsHandler::new(LargeStateGame::new(true));
+    ismcts.config_mut().rave = Some(100.0);
+    ismcts.run_iterations(2, ITERATIONS);
+    assert_eq!(2 * ITERATIONS, ismcts.total_visits());
+}
+
+#[test]
+pub fn fixed_determination_uses_true_state() {
+    let mut ismcts = IsmctsHandler::new(GuessGame::default());
+    ismcts.config_mut().fixed_determination = Some(GuessGame {
+        secret: 2,
+        guess: None,
+    });
+    ismcts.run_iterations(1, 500);
+    assert_eq!(Some(2), ismcts.best_move());
+    let (best, runner_up) = ismcts.top_two().unwrap();
+    assert_eq!(Some(1.0), best.mean_reward);
+    assert_eq!(Some(0.0), runner_up.mean_reward);
+}
+
+#[test]
+pub fn per_world_best_moves() {
+    let worlds = (0..4)
+        .map(|secret| GuessGame {
+            secret,
+            guess: None,
+        })
+        .collect();
+    let analysis = IsmctsHandler::per_world_analysis(GuessGame::default(), worlds, 1, 200);
+    assert_eq!(4, analysis.len());
+    for (world, best_move) in analysis {
+        assert_eq!(world.secret, best_move);
+    }
+}
+
+#[test]
+pub fn warmup_rollouts_average_into_one_visit() {
+    // The second player's random reply is the first player's number a tenth of the time
+    let mut ismcts = IsmctsHandler::new(TenMoveGame::default());
+    ismcts.config_mut().warmup_rollouts = 2000;
+    ismcts.run_iterations(1, 10);
+
+    let children = ismcts.root_node.children.read().unwrap();
+    assert_eq!(10, children.len());
+    for child in children.iter() {
+        let statistics = child.statistics.snapshot();
+        assert_eq!(1, statistics.visit_count);
+        assert!((statistics.mean() - 0.1).abs() < 0.05);
+        // Every reward is 0 or 1, so the mean of their squares is their mean
+        assert!((statistics.reward_sq - statistics.reward).abs() < 1e-9);
+        assert!((statistics.variance() - 0.09).abs() < 0.05);
+    }
+}
+
+#[test]
+pub fn focused_search_only_visits_forced_move() {
+    let ismcts = IsmctsHandler::new(TenMoveGame::default());
+    ismcts.run_focused(&7, 2, 100);
+
+    let children = ismcts.root_node.children.read().unwrap();
+    assert_eq!(1, children.len());
+    assert_eq!(Some(7), children[0].mov);
+    assert_eq!(200, children[0].statistics.snapshot().visit_count);
+    assert_eq!(10, children[0].children.read().unwrap().len());
+}
+
+#[test]
+pub fn lazy_node_iteration_is_breadth_first() {
+    let ismcts = IsmctsHandler::new(TenMoveGame::default());
+    ismcts.run_iterations(1, ITERATIONS);
+
+    let depths: Vec<_> = ismcts.nodes().map(|n| n.depth).collect();
+    assert!(depths.windows(2).all(|w| w[0] <= w[1]));
+    assert_eq!(ismcts.depth_histogram().iter().sum::<usize>(), depths.len());
+
+    let root = ismcts.nodes().next().unwrap();
+    assert_eq!(None, root.mov);
+    assert_eq!(ITERATIONS, root.visit_count);
+    let first_level_visits: usize = ismcts
+        .nodes()
+        .filter(|n| n.depth == 1)
+        .map(|n| n.visit_count)
+        .sum();
+    assert_eq!(ITERATIONS, first_level_visits);
+}
+
+#[test]
+pub fn adaptive_virtual_loss_is_released() {
+    let virtual_loss = VirtualLoss::Adaptive { base: 1.0 };
+    assert_eq!(0.0, virtual_loss.magnitude(0, 1));
+    assert!(virtual_loss.magnitude(0, 8) > virtual_loss.magnitude(0, 2));
+    assert!(virtual_loss.magnitude(100, 4) < virtual_loss.magnitude(1, 4));
+
+    let mut ismcts = IsmctsHandler::new(Nim::new(vec![3, 4, 5]));
+    ismcts.config_mut().virtual_loss = virtual_loss;
+    ismcts.run_iterations(4, ITERATIONS);
+    ismcts.run_focused(&NimMove { heap: 0, amount: 1 }, 4, ITERATIONS);
+
+    assert_eq!(8 * ITERATIONS, ismcts.total_visits());
+    ismcts.root_node.for_each_descendant(|node, _| {
+        assert_eq!(0, node.statistics.snapshot().in_flight);
+    });
+}
+
+#[test]
+pub fn root_children_sorted_by_key() {
+    let ismcts = IsmctsHandler::new(TenMoveGame::default());
+    ismcts.run_iterations(1, ITERATIONS);
+
+    let by_visits = ismcts.root_children_sorted_by(|c| c.visit_count);
+    assert_eq!(10, by_visits.len());
+    assert!(by_visits
+        .windows(2)
+        .all(|w| w[0].visit_count >= w[1].visit_count));
+    assert_eq!(ismcts.max_visits(), by_visits[0].visit_count);
+
+    let by_move = ismcts.root_children_sorted_by(|c| c.mov);
+    let moves: Vec<_> = by_move.iter().map(|c| c.mov.unwrap()).collect();
+    assert_eq!((0..10).rev().collect::<Vec<_>>(), moves);
+}
+
+#[test]
+pub fn global_budget_limits_lifetime_iterations() {
+    let mut ismcts = IsmctsHandler::new(TenMoveGame::default());
+    ismcts.run_iterations(2, 300);
+    ismcts.run_timed(1, Duration::from_millis(5));
+    let spent = ismcts.lifetime_iterations();
+    assert!(spent > 600);
+    assert_eq!(0, ismcts.remaining_budget(spent / 2));
+    assert_eq!(10, ismcts.remaining_budget(spent + 10));
+
+    let budget = spent + 250;
+    assert_eq!(100, ismcts.run_within_global_budget(2, 100, budget));
+    assert_eq!(25, ismcts.run_within_global_budget(2, 100, budget));
+    assert_eq!(0, ismcts.run_within_global_budget(2, 100, budget));
+    assert_eq!(0, ismcts.remaining_budget(budget));
+    // No threads searches on one instead of dividing by zero
+    assert_eq!(10, ismcts.run_within_global_budget(0, 100, budget + 10));
+
+    ismcts.reset(TenMoveGame::default());
+    assert_eq!(0, ismcts.lifetime_iterations());
+    assert_eq!(0, ismcts.total_visits());
+}
+
+#[test]
+pub fn result_distribution_of_terminal_results() {
+    let ismcts = IsmctsHandler::new(TenMoveGame::default());
+    ismcts.run_iterations(1, 10);
+    assert!(ismcts.result_distribution().is_empty());
+
+    let mut ismcts = IsmctsHandler::new(Nim::new(vec![1, 2]));
+    ismcts.config_mut().record_result_distribution = true;
+    ismcts.run_iterations(2, ITERATIONS);
+
+    let distribution = ismcts.result_distribution();
+    assert_eq!(2, distribution.len());
+    assert_eq!(0.0, distribution[0].0);
+    assert_eq!(1.0, distribution[1].0);
+    assert!((distribution[0].1 + distribution[1].1 - 1.0).abs() < 1e-9);
+}
+
+#[test]
+pub fn pessimistic_expansion_defers_untried_moves() {
+    let ismcts = IsmctsHandler::new(Nim::new(vec![30]));
+    ismcts.run_iterations(1, 2);
+    assert_eq!(2, ismcts.root_node.children.read().unwrap().len());
+
+    // The first child scores at least as well as an untried move with the worst sibling's mean
+    let mut ismcts = IsmctsHandler::new(Nim::new(vec![30]));
+    ismcts.config_mut().pessimistic_expansion = true;
+    ismcts.run_iterations(1, 2);
+    assert_eq!(1, ismcts.root_node.children.read().unwrap().len());
+    ismcts.run_iterations(1, ITERATIONS);
+    assert_eq!(
+        ITERATIONS + 2,
+        ismcts.root_node.statistics.snapshot().visit_count
+    );
+    assert_eq!(
+        Some(NimMove {
+            heap: 0,
+            amount: 30
+        }),
+        ismcts.best_move()
+    );
+}
+
+#[test]
+pub fn batch_search_finds_each_best_move() {
+    let states = vec![
+        Nim::new(vec![3]),
+        Nim::new(vec![0, 5]),
+        Nim::new(vec![0]),
+        Nim::new(vec![1, 2]),
+    ];
+    let best_moves = IsmctsHandler::batch_search(states, 2, ITERATIONS);
+    assert_eq!(
+        vec![
+            Some(NimMove { heap: 0, amount: 3 }),
+            Some(NimMove { heap: 1, amount: 5 }),
+            None,
+            Some(NimMove { heap: 1, amount: 1 }),
+        ],
+        best_moves
+    );
+}
+
+#[test]
+pub fn visits_are_consistent_with_children_under_threading() {
+    for n_threads in [2, 8] {
+        let mut ismcts = IsmctsHandler::new(TenMoveGame::default());
+        ismcts.config_mut().virtual_loss = VirtualLoss::Adaptive { base: 1.0 };
+        ismcts.run_iterations(n_threads, 2 * ITERATIONS);
+        assert_eq!(n_threads * 2 * ITERATIONS, ismcts.total_visits());
+
+        assert!(ismcts.visits_are_consistent());
+    }
+}
+
+#[test]
+pub fn atomic_statistics_lose_no_updates() {
+    let updates = |statistics: &AtomicStatistics, n: usize| {
+        for i in 0..n {
+            statistics.merge(&NodeStatistics {
+                visit_count: 1,
+                availability_count: 2,
+                reward: (i % 3) as f64,
+                reward_sq: ((i % 3) * (i % 3)) as f64,
+                ..Default::default()
+            });
+        }
+    };
+    let reference = AtomicStatistics::default();
+    for _ in 0..8 {
+        updates(&reference, 10_000);
+    }
+
+    let shared = AtomicStatistics::default();
+    std::thread::scope(|s| {
+        for _ in 0..8 {
+            s.spawn(|| updates(&shared, 10_000));
+        }
+    });
+    let (shared, reference) = (shared.snapshot(), reference.snapshot());
+    assert_eq!(reference.visit_count, shared.visit_count);
+    assert_eq!(reference.availability_count, shared.availability_count);
+    // Small integer sums are exact in any order
+    assert_eq!(reference.reward, shared.reward);
+    assert_eq!(reference.reward_sq, shared.reward_sq);
+}
+
+#[test]
+pub fn exploration_constant_scales_ucb1() {
+    let statistics = NodeStatistics {
+        visit_count: 2,
+        availability_count: 8,
+        reward: 1.0,
+        ..Default::default()
+    };
+    assert_eq!(0.5, statistics.ucb1(0.0));
+    let default_exploration = IsmctsHandler::new(TenMoveGame::default())
+        .config()
+        .exploration;
+    let expected = 0.5 + (2.0 * 8f64.ln() / 2.0).sqrt();
+    assert!((statistics.ucb1(default_exploration) - expected).abs() < 1e-12);
+
+    let ismcts = IsmctsHandler::with_exploration(TenMoveGame::default(), 0.5);
+    assert_eq!(0.5, ismcts.config().exploration);
+    ismcts.run_iterations(2, ITERATIONS);
+    assert_eq!(2 * ITERATIONS, ismcts.total_visits());
+}
+
+#[test]
+pub fn run_until_confident_stops_early() {
+    let ismcts = IsmctsHandler::new(Nim::new(vec![1, 2]));
+    let max_time = Duration::from_secs(2);
+    let start = Instant::now();
+    let iterations = ismcts.run_until_confident(2, max_time, 100);
+    // The visit gap can only exceed the remaining iterations after about half the time
+    assert!(start.elapsed() < max_time);
+    assert_eq!(ismcts.lifetime_iterations(), iterations);
+    assert_eq!(Some(NimMove { heap: 1, amount: 1 }), ismcts.best_move());
+
+    let decided = |visits: &[usize], iterations, elapsed_ms| {
+        let elapsed = Duration::from_millis(elapsed_ms);
+        best_move_decided(visits.to_vec(), iterations, elapsed, max_time, 100)
+    };
+    // 1000 more iterations are expected, enough for the runner-up to catch up
+    assert!(!decided(&[800, 200], 1000, 1000));
+    // Only 500 more
+    assert!(decided(&[1200, 300], 1500, 1500));
+    // A smaller gap than `min_gap` never stops the search
+    assert!(!decided(&[90, 0], 90, 1900));
+    assert!(decided(&[100], 100, 1900));
+    // The search ran over time
+    assert!(decided(&[150, 0], 150, 2100));
+}
+
+#[test]
+pub fn run_until_stable_stops_early() {
+    let ismcts = IsmctsHandler::with_seed(Nim::new(vec![1, 2]), 0);
+    let max_iterations = 100_000;
+    let iterations = ismcts.run_until_stable(1, 100, 3, 0.02, max_iterations);
+    assert_eq!(ismcts.lifetime_iterations(), iterations);
+    assert!(iterations >= 3 * 100);
+    assert!(iterations < max_iterations / 10, "{}", iterations);
+    assert_eq!(Some(NimMove { heap: 1, amount: 1 }), ismcts.best_move());
+
+    // Never stable, so the budget runs out
+    let ismcts = IsmctsHandler::new(Nim::new(vec![1, 2]));
+    assert_eq!(1000, ismcts.run_until_stable(2, 100, 3, 0.0, 1000));
+
+    // Empty batches never add up to the budget
+    assert_eq!(0, ismcts.run_until_stable(2, 0, 3, 0.0, 1000));
+    assert_eq!(0, ismcts.run_until_stable(0, 100, 3, 0.0, 1000));
+}
+
+#[test]
+pub fn search_summary_of_run_iterations() {
+    let ismcts = IsmctsHandler::new(Nim::new(vec![1, 2]));
+    let summary = ismcts.run_iterations_with_stats(4, ITERATIONS);
+    assert_eq!(4 * ITERATIONS, summary.total_iterations);
+    assert_eq!(3, summary.root_children.len());
+    assert_eq!(ismcts.max_visits(), summary.max_child_visits);
+    assert_eq!(ismcts.best_move(), summary.best_move);
+}
+
+#[test]
+pub fn progress_callback_reports_search() {
+    let ismcts = IsmctsHandler::new(Nim::new(vec![1, 2]));
+    let reports = Mutex::new(Vec::new());
+    ismcts.run_timed_with_progress(
+        2,
+        Duration::from_millis(200),
+        Duration::from_millis(20),
+        |progress| reports.lock().unwrap().push(progress.clone()),
+    );
+    let reports = reports.into_inner().unwrap();
+    assert!(reports.len() > 1);
+    assert!(reports
+        .windows(2)
+        .all(|w| w[0].total_visits <= w[1].total_visits && w[0].elapsed <= w[1].elapsed));
+    let last = reports.last().unwrap();
+    assert!(last.elapsed >= Duration::from_millis(200));
+    assert_eq!(ismcts.total_visits(), last.total_visits);
+    assert_eq!(ismcts.best_move(), last.best_move_so_far);
+}
+
+#[test]
+pub fn cancelled_search_returns_promptly() {
+    let ismcts = IsmctsHandler::new(Nim::new(vec![3, 4, 5]));
+    let cancel = Arc::new(AtomicBool::new(false));
+    let start = Instant::now();
+    std::thread::scope(|s| {
+        s.spawn(|| {
+            std::thread::sleep(Duration::from_millis(50));
+            cancel.store(true, Ordering::Relaxed);
+        });
+        ismcts.run_timed_cancellable(2, Duration::from_secs(10), Arc::clone(&cancel));
+    });
+    assert!(start.elapsed() < Duration::from_secs(2));
+    assert!(ismcts.lifetime_iterations() > 0);
+    assert_eq!(ismcts.lifetime_iterations(), ismcts.total_visits());
+    assert!(ismcts.best_move().is_some());
+}
+
+#[test]
+pub fn max_nodes_bounds_tree_size() {
+    let mut ismcts = IsmctsHandler::new(Nim::new(vec![1, 2, 4]));
+    ismcts.config_mut().max_nodes = Some(250);
+    ismcts.run_iterations(1, 5 * ITERATIONS);
+    assert!(ismcts.node_count() <= 250);
+    assert_eq!(ismcts.nodes().count(), ismcts.node_count());
+    // The only move leaving a nim-sum of zero
+    assert_eq!(Some(NimMove { heap: 2, amount: 1 }), ismcts.best_move());
+
+    let unbounded = IsmctsHandler::new(Nim::new(vec![1, 2, 4]));
+    unbounded.run_iterations(1, 5 * ITERATIONS);
+    assert!(unbounded.node_count() > 250);
+    assert_eq!(unbounded.nodes().count(), unbounded.node_count());
+}
+
+#[test]
+pub fn pruning_keeps_count_under_threading() {
+    let mut ismcts = IsmctsHandler::new(Nim::new(vec![2, 3, 4]));
+    ismcts.config_mut().max_nodes = Some(100);
+    ismcts.config_mut().transposition_table = true;
+    ismcts.run_iterations(8, ITERATIONS);
+    assert_eq!(ismcts.nodes().count(), ismcts.node_count());
+
+    // Only the transpositions of nodes still in the tree are kept
+    let mut shared = HashSet::new();
+    ismcts.root_node.for_each_descendant(|node, _| {
+        shared.insert(Arc::as_ptr(&node.statistics));
+    });
+    let transpositions = ismcts.transpositions.lock().unwrap();
+    assert!(transpositions
+        .values()
+        .all(|statistics| shared.contains(&Arc::as_ptr(statistics))));
+}
+
+#[test]
+pub fn rollout_policy_finds_winning_move() {
+    let game = Instrumented {
+        rollout: Some(optimal_nim_move),
+        ..Instrumented::new(Nim::new(vec![3, 4, 5]))
+    };
+    // Exploit the rollout results, since most moves lose against optimal replies
+    let ismcts = IsmctsHandler::with_exploration(game, 0.1);
+    ismcts.run_iterations(1, 200);
+
+    // The only move leaving a nim-sum of zero
+    assert_eq!(Some(NimMove { heap: 0, amount: 2 }), ismcts.best_move());
+
+    // The same policy given to the builder instead of the game
+    let ismcts = IsmctsBuilder::new()
+        .exploration(0.1)
+        .rollout(|nim: &Nim, _: &mut StdRng| optimal_nim_move(nim))
+        .build(Nim::new(vec![3, 4, 5]));
+    ismcts.run_iterations(1, 200);
+    assert_eq!(Some(NimMove { heap: 0, amount: 2 }), ismcts.best_move());
+}
+
+#[test]
+pub fn seeded_search_is_reproducible() {
+    let search = || {
+        let ismcts = IsmctsHandler::with_seed(GuessGame::default(), 7);
+        ismcts.run_iterations(1, ITERATIONS);
+        ismcts.run_iterations(1, ITERATIONS);
+        ismcts
+            .nodes()
+            .map(|n| (n.mov, n.visit_count, n.reward))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(search(), search());
+}
+
+#[test]
+pub fn single_thread_search_matches_run_iterations() {
+    let search = |configure: fn(&mut SearchConfig<KuhnPoker>), unlocked: bool| {
+        let mut ismcts = IsmctsHandler::with_seed(KuhnPoker::default(), 7);
+        configure(ismcts.config_mut());
+        if unlocked {
+            ismcts.run_iterations_single_thread(ITERATIONS);
+        } else {
+            ismcts.run_iterations(1, ITERATIONS);
+        }
+        ismcts
+            .nodes()
+            .map(|n| (n.mov, n.visit_count, n.availability_count, n.reward))
+            .collect::<Vec<_>>()
+    };
+    let configs: [fn(&mut SearchConfig<KuhnPoker>); 3] = [
+        |_| {},
+        |config| config.rave = Some(100.0),
+        |config| config.max_nodes = Some(8),
+    ];
+    for configure in configs {
+        assert_eq!(search(configure, false), search(configure, true));
+    }
+}
+
+#[test]
+pub fn single_thread_search_locks_a_tree_held_elsewhere() {
+    let mut ismcts = IsmctsHandler::new(KuhnPoker::default());
+    assert_eq!(Access::Exclusive, ismcts.exclusive_access());
+    // As by the threads of a forgotten `PonderHandle`
+    let held = Arc::clone(&ismcts.root_node);
+    assert_eq!(Access::Shared, ismcts.exclusive_access());
+    ismcts.run_iterations_single_thread(ITERATIONS);
+    assert_eq!(ITERATIONS, ismcts.lifetime_iterations());
+    drop(held);
+    assert_eq!(Access::Exclusive, ismcts.exclusive_access());
+}
+
+#[test]
+pub fn default_builder_matches_new() {
+    let search = |ismcts: IsmctsHandler<GuessGame>| {
+        ismcts.run_iterations(1, ITERATIONS);
+        ismcts
+            .nodes()
+            .map(|n| (n.mov, n.visit_count, n.reward))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        search(IsmctsHandler::with_seed(GuessGame::default(), 7)),
+        search(IsmctsBuilder::new().seed(7).build(GuessGame::default()))
+    );
+    let built: IsmctsHandler<GuessGame> = IsmctsHandler::builder().build(GuessGame::default());
+    let new = IsmctsHandler::new(GuessGame::default());
+    assert_eq!(new.config().exploration, built.config().exploration);
+    assert_eq!(new.config().selection, built.config().selection);
+}
+
+#[test]
+pub fn make_move_keeps_subtree_statistics() {
+    let mut ismcts = IsmctsHandler::new(Nim::new(vec![3, 4, 5]));
+    ismcts.run_iterations(2, ITERATIONS);
+    let old_root = Arc::clone(&ismcts.root_node);
+    let mov = ismcts.best_move().unwrap();
+    let prior_visits = old_root
+        .find_child(&mov, Access::Shared)
+        .unwrap()
+        .statistics
+        .snapshot()
+        .visit_count;
+
+    assert!(ismcts.visits_are_consistent());
+    ismcts.make_move(&mov).unwrap();
+    assert!(ismcts.root_node.parent.read().unwrap().is_none());
+    assert_eq!(
+        prior_visits,
+        ismcts.root_node.statistics.snapshot().visit_count
+    );
+
+    ismcts.run_iterations(2, ITERATIONS);
+    let root_visits = ismcts.root_node.statistics.snapshot().visit_count;
+    assert_eq!(prior_visits + 2 * ITERATIONS, root_visits);
+    // Backprop stops at the new root even while the old one is alive
+    assert_eq!(2 * ITERATIONS, old_root.statistics.snapshot().visit_count);
+
+    assert!(ismcts.visits_are_consistent());
+    let mov = ismcts.best_move().unwrap();
+    ismcts.make_move(&mov).unwrap();
+    assert!(ismcts.visits_are_consistent());
+    ismcts.run_iterations(1, ITERATIONS);
+    assert!(ismcts.root_node.statistics.snapshot().visit_count > ITERATIONS);
+}
+
+#[test]
+pub fn root_parallel_merges_thread_trees() {
+    let ismcts = IsmctsHandler::new(Nim::new(vec![1, 2]));
+    let best = ismcts.run_iterations_root_parallel(4, ITERATIONS, MergeStrategy::SumStatistics);
+    assert_eq!(best, ismcts.best_move());
+    assert_eq!(4 * ITERATIONS, ismcts.total_visits());
+    assert_eq!(
+        4 * ITERATIONS,
+        ismcts.root_node.statistics.snapshot().visit_count
+    );
+    assert_eq!(Some(NimMove { heap: 1, amount: 1 }), ismcts.best_move());
+
+    assert!(ismcts.visits_are_consistent());
+}
+
+#[test]
+pub fn merge_strategies_of_disagreeing_trees() {
+    // Two trees narrowly prefer move 1, while the third strongly prefers move 2
+    let trees = || {
+        [(10, 9), (10, 9), (1, 100)]
+            .iter()
+            .enumerate()
+            .map(|(index, &(visits_1, visits_2))| {
+                let root = Node::new_root();
+                let node_count = AtomicUsize::new(1);
+                for (mov, visit_count) in [(1, visits_1), (2, visits_2)] {
+                    let child =
+                        Arc::clone(&root).add_child(mov, 0, 1.0, Default::default(), &node_count);
+                    child.statistics.store(&NodeStatistics {
+                        visit_count,
+                        ..Default::default()
+                    });
+                }
+                (index, root)
+            })
+            .collect::<Vec<_>>()
+    };
+    let merge = |strategy| {
+        let ismcts = IsmctsHandler::new(TenMoveGame::default());
+        ismcts.merge_trees(trees(), strategy)
+    };
+    assert_eq!(Some(2), merge(MergeStrategy::SumStatistics));
+    assert_eq!(Some(1), merge(MergeStrategy::MajorityVote));
+    // Votes of 10/19 for move 1 twice outweigh 100/101 for move 2
+    assert_eq!(Some(1), merge(MergeStrategy::WeightedByVisits));
+
+    let ismcts = IsmctsHandler::new(Nim::new(vec![1, 2]));
+    assert_eq!(
+        Some(NimMove { heap: 1, amount: 1 }),
+        ismcts.run_iterations_root_parallel(4, ITERATIONS, MergeStrategy::MajorityVote)
+    );
+    assert_eq!(4 * ITERATIONS, ismcts.total_visits());
+}
+
+#[test]
+pub fn constant_virtual_loss_is_released() {
+    let virtual_loss = VirtualLoss::Constant(3.0);
+    assert_eq!(3.0, virtual_loss.magnitude(0, 1));
+    assert_eq!(3.0, virtual_loss.magnitude(100, 8));
+
+    let mut ismcts = IsmctsHandler::new(TenMoveGame::default());
+    ismcts.config_mut().virtual_loss = virtual_loss;
+    ismcts.run_iterations(8, ITERATIONS);
+    assert_eq!(8 * ITERATIONS, ismcts.total_visits());
+    ismcts.root_node.for_each_descendant(|node, _| {
+        assert_eq!(0, node.statistics.snapshot().in_flight);
+    });
+}
+
+/// Compare how much 8 threads concentrate on the most visited root child
+#[test]
+#[ignore]
+pub fn bench_virtual_loss_spread() {
+    let concentration = |virtual_loss| {
+        let trials = 50;
+        let mut total = 0.0;
+        for _ in 0..trials {
+            let mut ismcts = IsmctsHandler::new(Nim::new(vec![3, 4, 5]));
+            ismcts.config_mut().virtual_loss = virtual_loss;
+            ismcts.run_iterations(8, 50);
+            total += ismcts.max_visits() as f64 / ismcts.total_visits() as f64;
+        }
+        total / trials as f64
+    };
+    for virtual_loss in [
+        VirtualLoss::Disabled,
+        VirtualLoss::Constant(1.0),
+        VirtualLoss::Adaptive { base: 1.0 },
+    ] {
+        println!(
+            "{:?}: {:.3} of visits on the top child",
+            virtual_loss,
+            concentration(virtual_loss)
+        );
+    }
+}
+
+/// Iterations per second of a single threaded search of Kuhn poker, with the tree locked by
+/// `run_iterations` and unlocked by `run_iterations_single_thread`
+#[test]
+#[ignore]
+pub fn bench_single_thread() {
+    let iterations = 50_000;
+    let (mut locked, mut unlocked) = (Duration::MAX, Duration::MAX);
+    // Alternated, so that both searches run under the same load, keeping the fastest of each
+    for _ in 0..5 {
+        let ismcts = IsmctsHandler::new(KuhnPoker::default());
+        let start = Instant::now();
+        ismcts.run_iterations(1, iterations);
+        locked = locked.min(start.elapsed());
+
+        let mut ismcts = IsmctsHandler::new(KuhnPoker::default());
+        let start = Instant::now();
+        ismcts.run_iterations_single_thread(iterations);
+        unlocked = unlocked.min(start.elapsed());
+    }
+    println!(
+        "locked: {:.0} iterations/s, unlocked: {:.0} iterations/s",
+        iterations as f64 / locked.as_secs_f64(),
+        iterations as f64 / unlocked.as_secs_f64()
+    );
+}
+
+#[test]
+pub fn arena_search_finds_winning_move() {
+    let mut arena = ArenaIsmcts::new(Nim::new(vec![1, 2]));
+    arena.run_iterations(ITERATIONS);
+    assert_eq!(Some(NimMove { heap: 1, amount: 1 }), arena.best_move());
+    assert_eq!(
+        ITERATIONS,
+        arena.move_visits().iter().map(|(_, v)| v).sum::<usize>()
+    );
+
+    let search = || {
+        let mut arena = ArenaIsmcts::with_seed(GuessGame::default(), 7);
+        arena.run_iterations(ITERATIONS);
+        (arena.node_count(), arena.move_visits())
+    };
+    assert_eq!(search(), search());
+}
+
+/// Compare iterations per second of a game with a large state when cloning it for each
+/// iteration and when unmaking each iteration's moves
+#[test]
+#[ignore]
+pub fn bench_unmake_move() {
+    for unmake in [false, true] {
+        let ismcts = IsmctsHandler::new(Instrumented::new(LargeStateGame::new(unmake)));
+        let counts = Arc::clone(&ismcts.state().counts);
+        let iterations = 20_000;
+        counts.clones.store(0, Ordering::SeqCst);
+        let start = Instant::now();
+        ismcts.run_iterations(1, iterations);
+        println!(
+            "unmake {}: {:.0} iterations/s, {} clones",
+            unmake,
+            iterations as f64 / start.elapsed().as_secs_f64(),
+            counts.clones.load(Ordering::SeqCst)
+        );
+    }
+}
+
+/// Compare iterations per second of the shared tree and the arena on one thread
+#[test]
+#[ignore]
+pub fn bench_arena() {
+    let iterations = 50_000;
+    let start = Instant::now();
+    IsmctsHandler::new(TenMoveGame::default()).run_iterations(1, iterations);
+    let shared = start.elapsed();
+    let start = Instant::now();
+    ArenaIsmcts::new(TenMoveGame::default()).run_iterations(iterations);
+    let arena = start.elapsed();
+    println!(
+        "shared tree: {:.0} iterations/s, arena: {:.0} iterations/s",
+        iterations as f64 / shared.as_secs_f64(),
+        iterations as f64 / arena.as_secs_f64()
+    );
+}
+
+/// Compare iterations per second of a cheap game when checking the clock less often
+#[test]
+#[ignore]
+pub fn bench_time_check_every() {
+    for check_every in [1, 16, 256] {
+        let mut ismcts = IsmctsHandler::new(Nim::new(vec![1, 2]));
+        ismcts.config_mut().time_check_every = check_every;
+        ismcts.run_timed(1, Duration::from_secs(1));
+        println!(
+            "check every {}: {} iterations/s",
+            check_every,
+            ismcts.lifetime_iterations()
+        );
+    }
+}
+
+#[test]
+pub fn progressive_widening_limits_children() {
+    let widening = ProgressiveWidening { k: 1.0, alpha: 0.5 };
+    assert_eq!(1, widening.max_children(1));
+    assert_eq!(10, widening.max_children(100));
+
+    let mut ismcts = IsmctsHandler::new(Nim::new(vec![100]));
+    ismcts.config_mut().progressive_widening = Some(widening);
+    ismcts.run_iterations(1, ITERATIONS);
+
+    let n_children = ismcts.root_node.children.read().unwrap().len();
+    assert!(n_children > 1);
+    assert!(n_children <= widening.max_children(ITERATIONS));
+    assert!(ismcts.depth_histogram().len() > 3);
+}
+
+#[test]
+pub fn rave_tracks_all_moves_as_first() {
+    let mut ismcts = IsmctsHandler::new(Nim::new(vec![2, 3]));
+    ismcts.config_mut().rave = Some(100.0);
+    ismcts.run_iterations(2, ITERATIONS);
+
+    assert_eq!(2 * ITERATIONS, ismcts.total_visits());
+    ismcts.root_node.for_each_descendant(|node, depth| {
+        let statistics = node.statistics.snapshot();
+        // A node's own move is always played after its parent
+        if depth > 0 {
+            assert!(statistics.amaf_visits >= statistics.visit_count);
+        }
+    });
+    // The first player usually moves again later in a simulation
+    let amaf_visits: usize = ismcts
+        .root_node
+        .children
+        .read()
+        .unwrap()
+        .iter()
+        .map(|c| c.statistics.snapshot().amaf_visits)
+        .sum();
+    assert!(amaf_visits > ismcts.total_visits());
+}
+
+#[test]
+pub fn rave_checks_the_jack_sooner_in_kuhn_poker() {
+    // Betting the jack only wins if the opponent folds, and calling later with it never wins,
+    // which RAVE counts against betting as well
+    let checks = |rave| {
+        (0..300)
+            .filter(|_| {
+                let mut ismcts = IsmctsHandler::new(KuhnPoker {
+                    cards: [0, 1],
+                    bets: Vec::new(),
+                });
+                ismcts.config_mut().reward_bounds = Some((-2.0, 2.0));
+                ismcts.config_mut().rave = rave;
+                ismcts.run_iterations(1, 24);
+                ismcts.best_move() == Some(false)
+            })
+            .count()
+    };
+    // About 250 and 295 of the searches check
+    let (plain, rave) = (checks(None), checks(Some(100.0)));
+    assert!(rave > 280, "{} {}", rave, plain);
+    assert!(rave > plain, "{} {}", rave, plain);
+}
+
+#[test]
+pub fn puct_uses_move_priors() {
+    let ismcts = IsmctsHandler::new_puct(Nim::new(vec![1, 2]), 2.0);
+    assert_eq!(Selection::Puct, ismcts.config().selection);
+    ismcts.run_iterations(2, ITERATIONS);
+
+    assert_eq!(2 * ITERATIONS, ismcts.total_visits());
+    assert_eq!(Some(NimMove { heap: 1, amount: 1 }), ismcts.best_move());
+    for child in ismcts.root_node.children.read().unwrap().iter() {
+        assert_eq!(1.0 / 3.0, child.prior.load());
+    }
+}
+
+#[test]
+pub fn transposition_table_shares_statistics() {
+    let mut ismcts = IsmctsHandler::new(Nim::new(vec![2, 2]));
+    ismcts.config_mut().transposition_table = true;
+    ismcts.run_iterations(2, ITERATIONS);
+
+    let take = |heap| NimMove { heap, amount: 1 };
+    let first_then_second = ismcts
+        .root_node
+        .find_child(&take(0), Access::Shared)
+        .and_then(|n| n.find_child(&take(1), Access::Shared))
+        .unwrap();
+    let second_then_first = ismcts
+        .root_node
+        .find_child(&take(1), Access::Shared)
+        .and_then(|n| n.find_child(&take(0), Access::Shared))
+        .unwrap();
+    assert!(Arc::ptr_eq(
+        &first_then_second.statistics,
+        &second_then_first.statistics
+    ));
+    assert!(first_then_second.statistics.snapshot().visit_count > 0);
+
+    let ismcts = IsmctsHandler::new(Nim::new(vec![2, 2]));
+    ismcts.run_iterations(2, ITERATIONS);
+    let first_then_second = ismcts
+        .root_node
+        .find_child(&take(0), Access::Shared)
+        .and_then(|n| n.find_child(&take(1), Access::Shared))
+        .unwrap();
+    let second_then_first = ismcts
+        .root_node
+        .find_child(&take(1), Access::Shared)
+        .and_then(|n| n.find_child(&take(0), Access::Shared))
+        .unwrap();
+    assert!(!Arc::ptr_eq(
+        &first_then_second.statistics,
+        &second_then_first.statistics
+    ));
+}
+
+#[test]
+pub fn make_move_drops_unreachable_transpositions() {
+    let mut ismcts = IsmctsHandler::new(Nim::new(vec![2, 3]));
+    ismcts.config_mut().transposition_table = true;
+    ismcts.run_iterations(1, ITERATIONS);
+    let before = ismcts.transpositions.lock().unwrap().len();
+
+    ismcts.make_move(&NimMove { heap: 1, amount: 3 }).unwrap();
+    let mut shared = HashSet::new();
+    ismcts.root_node.for_each_descendant(|node, _| {
+        shared.insert(Arc::as_ptr(&node.statistics));
+    });
+    let transpositions = ismcts.transpositions.lock().unwrap();
+    assert!(transpositions.len() < before);
+    assert!(transpositions
+        .values()
+        .all(|statistics| shared.contains(&Arc::as_ptr(statistics))));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+pub fn save_and_load_round_trip() {
+    let ismcts = IsmctsHandler::new(Nim::new(vec![3, 4, 5]));
+    ismcts.run_iterations(2, ITERATIONS);
+    let mut saved = Vec::new();
+    ismcts.save(&mut saved).unwrap();
+
+    let mut loaded = IsmctsHandler::new(Nim::new(vec![3, 4, 5]));
+    loaded.load(saved.as_slice()).unwrap();
+    assert_eq!(ismcts.best_move(), loaded.best_move());
+    let summary = |ismcts: &IsmctsHandler<Nim>| {
+        ismcts
+            .nodes()
+            .map(|n| (n.mov, n.depth, n.visit_count, n.reward))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(summary(&ismcts), summary(&loaded));
+
+    // Backprop reaches the loaded root through the rebuilt parent links
+    loaded.run_iterations(1, ITERATIONS);
+    assert_eq!(
+        3 * ITERATIONS,
+        loaded.root_node.statistics.snapshot().visit_count
+    );
+    assert!(loaded.load(&b"[]"[..]).is_err());
+}
+
+#[test]
+pub fn dot_export_to_max_depth() {
+    let ismcts = IsmctsHandler::new(Nim::new(vec![1, 2]));
+    ismcts.run_iterations(1, ITERATIONS);
+
+    let dot = ismcts.to_dot(1);
+    assert!(dot.starts_with("digraph ismcts {"));
+    assert_eq!(4, dot.matches("[label=").count());
+    assert_eq!(3, dot.matches(" -> ").count());
+    assert_eq!(3, dot.matches("ucb1: ").count());
+    assert!(dot.contains("NimMove { heap: 1, amount: 1 }"));
+    assert_eq!(
+        ismcts.depth_histogram().iter().sum::<usize>(),
+        ismcts.to_dot(usize::MAX).matches("[label=").count()
+    );
+}
+
+#[test]
+pub fn move_statistics_cover_root_moves() {
+    let game = Nim::new(vec![2, 3]);
+    let ismcts = IsmctsHandler::new(game.clone());
+    ismcts.run_iterations(2, ITERATIONS);
+
+    let statistics = ismcts.move_statistics();
+    let available_moves = game.available_moves();
+    assert_eq!(available_moves.len(), statistics.len());
+    assert!(available_moves
+        .iter()
+        .all(|m| statistics.iter().any(|(mov, _, _)| mov == m)));
+    assert!(statistics.windows(2).all(|w| w[0].1 >= w[1].1));
+    assert_eq!(ismcts.max_visits(), statistics[0].1);
+    assert_eq!(
+        2 * ITERATIONS,
+        statistics
+            .iter()
+            .map(|(_, visits, _)| visits)
+            .sum::<usize>()
+    );
+    assert!(statistics
+        .iter()
+        .all(|(_, _, mean)| (0.0..=1.0).contains(mean)));
+}
+
+#[test]
+pub fn sample_move_follows_visit_counts() {
+    let mut rng = StdRng::seed_from_u64(1);
+    let ismcts = IsmctsHandler::new(TenMoveGame::default());
+    assert_eq!(None, ismcts.sample_move(1.0, &mut rng));
+    let only = add_visited_child(&ismcts, 0, 0, 0.0);
+    assert_eq!(Some(0), ismcts.sample_move(1.0, &mut rng));
+
+    only.statistics.visit_count.store(10, Ordering::Relaxed);
+    add_visited_child(&ismcts, 1, 30, 0.0);
+    add_visited_child(&ismcts, 2, 60, 0.0);
+    let n_samples = 10000;
+    let mut counts = [0; 3];
+    for _ in 0..n_samples {
+        counts[usize::from(ismcts.sample_move(1.0, &mut rng).unwrap())] += 1;
+    }
+    for (count, visits) in counts.iter().zip(&[10, 30, 60]) {
+        let expected = *visits as f64 / 100.0;
+        assert!((*count as f64 / n_samples as f64 - expected).abs() < 0.02);
+    }
+
+    assert_eq!(Some(2), ismcts.sample_move(0.0, &mut rng));
+    assert!((0..100).all(|_| ismcts.sample_move(0.01, &mut rng) == Some(2)));
+}
+
+#[test]
+pub fn best_move_criteria() {
+    let ismcts = IsmctsHandler::new(TenMoveGame::default());
+    add_visited_child(&ismcts, 0, 50, 25.0);
+    add_visited_child(&ismcts, 1, 20, 14.0);
+    let lucky = add_visited_child(&ismcts, 2, 2, 2.0);
+
+    assert_eq!(Some(0), ismcts.best_move_by(BestMoveCriterion::MostVisits));
+    let highest_reward =
+        |min_visits| ismcts.best_move_by(BestMoveCriterion::HighestReward { min_visits });
+    assert_eq!(Some(2), highest_reward(0));
+    assert_eq!(Some(1), highest_reward(5));
+    assert_eq!(None, highest_reward(100));
+    assert_eq!(None, ismcts.best_move_by(BestMoveCriterion::RobustMax));
+
+    lucky.statistics.reward.store(0.0);
+    add_visited_child(&ismcts, 3, 0, 0.0);
+    add_visited_child(&ismcts, 4, 49, 0.0);
+    assert_eq!(Some(1), highest_reward(0));
+    ismcts
+        .root_node
+        .find_child(&1, Access::Shared)
+        .unwrap()
+        .statistics
+        .store(&NodeStatistics {
+            visit_count: 20,
+            reward: 5.0,
+            ..Default::default()
+        });
+    assert_eq!(Some(0), ismcts.best_move_by(BestMoveCriterion::RobustMax));
+}
+
+#[test]
+pub fn unvisited_children_score_infinite() {
+    // Added by an iteration which hasn't backpropagated yet
+    let unvisited = NodeStatistics {
+        in_flight: 1,
+        ..Default::default()
+    };
+    let policies: [&dyn SelectionPolicy; 3] = [
+        &Ucb1 { exploration: 1.0 },
+        &Ucb1Tuned,
+        &Puct { exploration: 1.0 },
+    ];
+    for policy in policies {
+        for virtual_loss in [0.0, 1.0] {
+            let score = policy.score_with_virtual_loss(&unvisited, 3, virtual_loss, 0.5);
+            assert_eq!(f64::INFINITY, score, "{:?}", policy);
+        }
+    }
+
+    // RAVE trusts only the all-moves-as-first mean before the first visit
+    let unvisited = NodeStatistics {
+        amaf_visits: 4,
+        amaf_reward: 3.0,
+        ..Default::default()
+    };
+    assert_eq!(0.75, unvisited.rave_score(f64::INFINITY, 100.0));
+    let visited = NodeStatistics {
+        visit_count: 1,
+        ..unvisited
+    };
+    assert!(visited.rave_score(0.5, 100.0).is_finite());
+}
+
+#[test]
+pub fn ucb1_tuned_without_availability() {
+    // A parent with no visits yet, as `UcbAvailability::Parent` may give under `backup_depth`
+    let statistics = NodeStatistics {
+        visit_count: 2,
+        reward: 1.0,
+        reward_sq: 1.0,
+        ..Default::default()
+    };
+    assert_eq!(0.5, statistics.ucb1_tuned_with_virtual_loss(0, 0.0));
+    assert_eq!(0.5, statistics.ucb1_with_availability(0, 1.0));
+}
+
+#[test]
+pub fn reward_variance_and_confidence() {
+    // Rewards 1, 0, 1, 1
+    let statistics = NodeStatistics {
+        visit_count: 4,
+        availability_count: 8,
+        reward: 3.0,
+        reward_sq: 3.0,
+        ..Default::default()
+    };
+    assert!((statistics.variance() - 0.1875).abs() < 1e-12);
+    let log_availability = 8f64.ln();
+    let variance_bound = (0.1875 + (2.0 * log_availability / 4.0).sqrt()).min(0.25);
+    let expected = 0.75 + (log_availability / 4.0 * variance_bound).sqrt();
+    assert!((statistics.ucb1_tuned() - expected).abs() < 1e-12);
+
+    let ismcts = IsmctsHandler::new(TenMoveGame::default());
+    let sampled = add_visited_child(&ismcts, 0, 4, 3.0);
+    sampled.statistics.reward_sq.store(3.0);
+    add_visited_child(&ismcts, 1, 1, 1.0);
+    add_visited_child(&ismcts, 2, 0, 0.0);
+    let confidence = ismcts.move_confidence();
+    assert_eq!(0, confidence[0].0);
+    assert_eq!(0.75, confidence[0].1);
+    // Sample variance of 0.25 over 4 visits
+    assert!((confidence[0].2 - 1.96 * 0.25f64.sqrt() / 2.0).abs() < 1e-12);
+    assert_eq!((1, 1.0, f64::INFINITY), confidence[1]);
+    assert_eq!((2, 0.0, f64::INFINITY), confidence[2]);
+
+    let mut ismcts = IsmctsHandler::new(Nim::new(vec![1, 2]));
+    ismcts.config_mut().selection = Selection::Ucb1Tuned;
+    ismcts.run_iterations(1, 2000);
+    assert_eq!(Some(NimMove { heap: 1, amount: 1 }), ismcts.best_move());
+}
+
+#[test]
+pub fn each_player_maximizes_own_reward() {
+    let ismcts = IsmctsHandler::new(CopycatGame::default());
+    ismcts.run_iterations(1, ITERATIONS);
+    assert_eq!(Some(0), ismcts.best_move());
+
+    for (first, second) in [(0, 0), (0, 1), (1, 0), (1, 1)] {
+        let ismcts = IsmctsHandler::new(CopycatGame {
+            picks: vec![first, second],
+        });
+        ismcts.run_iterations(1, 100);
+        assert_eq!(Some(first), ismcts.best_move());
+    }
+}
+
+#[test]
+pub fn multiple_observers_hide_moves() {
+    // With a single tree the guesser sees the pick, so picking looks hopeless
+    let ismcts = IsmctsHandler::new(HiddenPickGame::default());
+    ismcts.run_iterations(1, 2000);
+    let (_, _, mean) = ismcts.move_statistics()[0];
+    assert!(mean < 0.5, "{}", mean);
+
+    // With a tree each, the guesser can do no better than chance. Both trees still select
+    // deterministically once every move is tried, and in about one search in a hundred the
+    // guesser's cycle through its moves falls into step with the picker's, so the picker's
+    // mean is averaged over many searches.
+    let mut total = 0.0;
+    for _ in 0..20 {
+        let ismcts = IsmctsHandlerMo::new(HiddenPickGame::default(), vec![0, 1]);
+        ismcts.run_iterations(1, 2000);
+        let (_, _, mean) = ismcts.move_statistics()[0];
+        total += mean;
+        // The picker's tree has every pick followed by a hidden guess, the guesser's a hidden
+        // pick followed by every guess
+        assert_eq!((1 + 4 + 4) + (1 + 1 + 4), ismcts.node_count());
+    }
+    assert!(total / 20.0 > 0.6, "{}", total / 20.0);
+}
+
+#[test]
+pub fn multiple_observers_agree_on_kuhn_poker() {
+    // Facing a bet, the second player calls with the king and folds with the jack, whose
+    // results are certain. Every move is public, so one tree decides like a tree each.
+    for (card, best, mean) in [(2, true, 2.0), (0, false, -1.0)] {
+        let game = KuhnPoker {
+            cards: [1, card],
+            bets: vec![true],
+        };
+        let single = IsmctsHandler::with_seed(game.clone(), 0);
+        single.run_iterations(1, 1000);
+        let mut multiple = IsmctsHandlerMo::new(game, vec![0, 1]);
+        multiple.config_mut().seed = Some(0);
+        multiple.run_iterations(1, 1000);
+
+        assert_eq!(Some(best), single.best_move());
+        assert_eq!(Some(best), multiple.best_move());
+        assert_eq!(mean, single.move_statistics()[0].2);
+        assert_eq!(mean, multiple.move_statistics()[0].2);
+    }
+}
+
+#[test]
+pub fn tree_depth_and_size_of_full_tree() {
+    // Explore so much that every move gets tried
+    let ismcts = IsmctsHandler::with_exploration(TenMoveGame::default(), 1000.0);
+    assert_eq!(0, ismcts.tree_depth());
+    assert_eq!(1, ismcts.node_count());
+
+    ismcts.run_iterations(1, 5000);
+    // Ten moves on each of the turns up to and including TOTAL_TURNS
+    assert_eq!(TOTAL_TURNS + 1, ismcts.tree_depth());
+    let full_size: usize = (0..=TOTAL_TURNS + 1)
+        .map(|depth| 10usize.pow(depth as u32))
+        .sum();
+    assert_eq!(full_size, ismcts.node_count());
+    assert_eq!(full_size, ismcts.nodes().count());
+}
+
+#[test]
+pub fn discount_scales_rewards_by_distance() {
+    let mut ismcts = IsmctsHandler::new(TenMoveGame::default());
+    ismcts.config_mut().discount = 0.5;
+    let child =
+        Arc::clone(&ismcts.root_node).add_child(1, 1, 1.0, Default::default(), &ismcts.node_count);
+    let win = TenMoveGame { moves: vec![0, 1] };
+    for distance in 0..3 {
+        child.update(
+            std::slice::from_ref(&win),
+            ismcts.config(),
+            distance,
+            Access::Shared,
+        );
+    }
+    let statistics = child.statistics.snapshot();
+    assert_eq!(3, statistics.visit_count);
+    assert_eq!(1, statistics.leaf_visits);
+    assert_eq!(1.0 + 0.5 + 0.25, statistics.reward);
+    assert_eq!(1.0 + 0.25 + 0.0625, statistics.reward_sq);
+}
+
+#[test]
+pub fn simultaneous_moves_converge_to_equilibrium() {
+    let converges = || {
+        let ismcts = IsmctsHandler::new(RockPaperScissors::default());
+        ismcts.run_iterations(1, 10000);
+        let children = ismcts.root_node.children.read().unwrap();
+        let mut second_player_visits = [0; 3];
+        let mut near_equilibrium = true;
+        for child in children.iter() {
+            let statistics = child.statistics.snapshot();
+            // Seeing the first move would let the second player win every time
+            near_equilibrium &= (statistics.mean() - 0.5).abs() < 0.1;
+            let frequency = statistics.visit_count as f64 / 10000.0;
+            near_equilibrium &= (frequency - 1.0 / 3.0).abs() < 0.1;
+            for reply in child.children.read().unwrap().iter() {
+                second_player_visits[usize::from(reply.mov.unwrap())] +=
+                    reply.statistics.snapshot().visit_count;
+            }
+        }
+        near_equilibrium
+            && second_player_visits
+                .iter()
+                .all(|&visits| (visits as f64 / 10000.0 - 1.0 / 3.0).abs() < 0.1)
+    };
+    // About one search in a hundred has both players cycling through their moves in step
+    let converged = (0..10).filter(|_| converges()).count();
+    assert!(converged >= 8, "{}", converged);
+}
+
+#[test]
+pub fn chance_nodes_average_over_outcomes() {
+    // Explore so much that every call gets about the same number of visits
+    let ismcts = IsmctsHandler::with_exploration(DieGame::default(), 100.0);
+    ismcts.run_iterations(1, 12000);
+    assert_eq!(1, ismcts.tree_depth());
+    for (call, visits, mean) in ismcts.move_statistics() {
+        let expected = f64::from(7 - call) / 6.0;
+        assert!(visits > 1000);
+        assert!((mean - expected).abs() < 0.05, "{}: {}", call, mean);
+    }
+}
+
+#[test]
+pub fn clear_tree_keeps_position() {
+    let mut ismcts = IsmctsHandler::new(TenMoveGame { moves: vec![3] });
+    ismcts.run_iterations(1, 1000);
+    ismcts.run_iterations(1, 1000);
+    assert_eq!(2000, ismcts.lifetime_iterations());
+
+    ismcts.clear_tree();
+    assert_eq!(0, ismcts.lifetime_iterations());
+    assert!(ismcts.root_node.children.read().unwrap().is_empty());
+    assert_eq!(1, ismcts.node_count());
+    assert_eq!(vec![3], ismcts.state().moves);
+}
+
+#[test]
+pub fn grafted_tree_decides_best_move() {
+    let mut pondering = IsmctsHandler::new(Nim::new(vec![1, 1, 2]));
+    pondering.run_iterations(1, 5000);
+    // A mistake, after which the only winning reply leaves two heaps of one
+    pondering
+        .make_move(&NimMove { heap: 0, amount: 1 })
+        .unwrap();
+    let visits = pondering.total_visits();
+
+    // Only a tree of the same position is taken
+    let mut ismcts = IsmctsHandler::new(Nim::new(vec![1, 1, 2]));
+    assert!(!ismcts.graft(IsmctsHandler::new(Nim::new(vec![1, 1]))));
+    assert_eq!(1, ismcts.node_count());
+
+    let mut ismcts = IsmctsHandler::new(pondering.state().clone());
+    assert_eq!(None, ismcts.best_move());
+    assert!(ismcts.graft(pondering));
+    assert!(ismcts.root_node.parent.read().unwrap().is_none());
+    assert_eq!(visits, ismcts.total_visits());
+    assert_eq!(ismcts.nodes().count(), ismcts.node_count());
+    let winning_reply = Some(NimMove { heap: 2, amount: 1 });
+    assert_eq!(winning_reply, ismcts.best_move());
+    ismcts.run_iterations(1, 10);
+    assert_eq!(winning_reply, ismcts.best_move());
+
+    // Without state hashes, only an unchecked graft takes the tree
+    let searched = || {
+        let source = IsmctsHandler::new(GuessGame::default());
+        source.run_iterations(1, 100);
+        source
+    };
+    let mut ismcts = IsmctsHandler::new(GuessGame::default());
+    assert!(!ismcts.graft(searched()));
+    assert_eq!(0, ismcts.total_visits());
+    ismcts.graft_unchecked(searched());
+    assert_eq!(100, ismcts.total_visits());
+}
+
+#[test]
+pub fn more_determinizations_steady_best_move() {
+    // Facing a bet with the queen, calling breaks even against the hidden jack or king, which
+    // beats folding
+    let calls = |determinizations| {
+        (0..50)
+            .filter(|&seed| {
+                let game = KuhnPoker {
+                    cards: [0, 1],
+                    bets: vec![true],
+                };
+                let mut ismcts = IsmctsHandler::with_seed(game, seed);
+                ismcts.config_mut().determinizations_per_iter = determinizations;
+                ismcts.run_iterations(1, 30);
+                ismcts.best_move() == Some(true)
+            })
+            .count()
+    };
+    let (single, several) = (calls(1), calls(16));
+    assert!(several > 45, "{}", several);
+    assert!(several > single, "{} {}", several, single);
+}
+
+#[test]
+pub fn first_play_urgency_revisits_winning_move() {
+    let winning_move = NimMove {
+        heap: 1,
+        amount: 19,
+    };
+    let search = |ismcts: IsmctsHandler<Nim>| {
+        let child = Arc::clone(&ismcts.root_node).add_child(
+            winning_move,
+            0,
+            1.0,
+            Default::default(),
+            &ismcts.node_count,
+        );
+        child.statistics.store(&NodeStatistics {
+            visit_count: 10,
+            availability_count: 10,
+            reward: 10.0,
+            ..Default::default()
+        });
+        ismcts.run_iterations(1, 20);
+        let n_children = ismcts.root_node.children.read().unwrap().len();
+        (n_children, child.statistics.snapshot().visit_count)
+    };
+
+    // Every one of the 21 moves is tried before returning to the winning move
+    assert_eq!((21, 10), search(IsmctsHandler::new(Nim::new(vec![1, 20]))));
+    let ismcts = IsmctsHandler::builder()
+        .fpu(0.5)
+        .build(Nim::new(vec![1, 20]));
+    assert_eq!((1, 30), search(ismcts));
+}
+
+#[test]
+pub fn full_expansion_adds_every_move() {
+    let mut ismcts = IsmctsHandler::new(TenMoveGame::default());
+    ismcts.config_mut().expansion = ExpansionPolicy::Full;
+    ismcts.run_iterations(1, 1);
+    let children = ismcts.root_node.children.read().unwrap();
+    assert_eq!(10, children.len());
+    assert_eq!(11, ismcts.node_count());
+    let visits: Vec<_> = children
+        .iter()
+        .map(|c| c.statistics.snapshot().visit_count)
+        .collect();
+    assert_eq!(1, visits.iter().sum::<usize>());
+    drop(children);
+
+    // Concurrent expansions of the same node add each move once
+    ismcts.run_iterations(4, 100);
+    let children = ismcts.root_node.children.read().unwrap();
+    assert_eq!(10, children.len());
+    for child in children.iter() {
+        assert_eq!(10, child.children.read().unwrap().len());
+    }
+}
+
+#[test]
+#[cfg(all(feature = "threads", not(target_arch = "wasm32")))]
+pub fn simultaneous_expansions_add_each_child_once() {
+    let mut ismcts = IsmctsHandler::new(TenMoveGame::default());
+    ismcts.config_mut().expansion = ExpansionPolicy::Full;
+    // Both threads add all ten moves to the empty root at once
+    ismcts.config_mut().expansion_barrier = Some(Arc::new(std::sync::Barrier::new(2)));
+    ismcts.run_iterations(2, 1);
+    assert_eq!(10, ismcts.root_node.children.read().unwrap().len());
+    assert_eq!(11, ismcts.node_count());
+    assert_eq!(2, ismcts.total_visits());
+}
+
+#[test]
+pub fn ucb1_of_unvisited_node_is_infinite() {
+    let unvisited = NodeStatistics {
+        availability_count: 3,
+        in_flight: 1,
+        ..Default::default()
+    };
+    assert_eq!(f64::INFINITY, unvisited.ucb1(1.0));
+    assert_eq!(f64::INFINITY, unvisited.ucb1_with_virtual_loss(3, 1.0, 1.0));
+    let never_available = NodeStatistics {
+        visit_count: 1,
+        reward: 1.0,
+        ..Default::default()
+    };
+    assert_eq!(1.0, never_available.ucb1(1.0));
+}
+
+#[test]
+pub fn best_move_or_random_before_search() {
+    let mut rng = thread_rng();
+    let ismcts = IsmctsHandler::new(Nim::new(vec![3, 4, 5]));
+    assert_eq!(None, ismcts.best_move());
+    let mov = ismcts.best_move_or_random(&mut rng).unwrap();
+    assert!(ismcts.state().available_moves().contains(&mov));
+
+    let finished = IsmctsHandler::new(Nim::new(vec![0]));
+    assert_eq!(None, finished.best_move_or_random(&mut rng));
+
+    ismcts.run_iterations(1, 100);
+    assert_eq!(ismcts.best_move(), ismcts.best_move_or_random(&mut rng));
+}
+
+#[test]
+pub fn leaf_parallel_steadies_best_move() {
+    let winning_moves = |leaf_parallel| {
+        (0..100)
+            .filter(|_| {
+                let mut ismcts = IsmctsHandler::new(Nim::new(vec![2, 3]));
+                ismcts.config_mut().leaf_parallel = leaf_parallel;
+                ismcts.run_iterations(1, 60);
+                ismcts.best_move() == Some(NimMove { heap: 1, amount: 1 })
+            })
+            .count()
+    };
+    // About 98 and 82 of the searches find the winning move
+    let (single, parallel) = (winning_moves(1), winning_moves(16));
+    assert!(parallel > 90, "{} {}", parallel, single);
+    assert!(parallel > single, "{} {}", parallel, single);
+}
+
+#[test]
+pub fn reward_bounds_scale_rewards() {
+    let search = |rewards, reward_bounds, seed| {
+        let nim = Instrumented {
+            rewards,
+            ..Instrumented::new(Nim::new(vec![1, 2, 4]))
+        };
+        let mut ismcts = IsmctsHandler::with_seed(nim, seed);
+        ismcts.config_mut().reward_bounds = reward_bounds;
+        ismcts.run_iterations(1, 1000);
+        ismcts
+    };
+    let winning_move = Some(NimMove { heap: 2, amount: 1 });
+
+    // Scaled rewards search exactly like rewards between 0 and 1
+    let scaled = search((0.0, 100.0), Some((0.0, 100.0)), 0);
+    let unit = search((0.0, 1.0), None, 0);
+    assert_eq!(unit.move_statistics(), scaled.move_statistics());
+
+    // Unscaled, exploration is too weak to find the winning move reliably
+    let winning_searches = |reward_bounds| {
+        (0..20)
+            .filter(|&seed| search((0.0, 100.0), reward_bounds, seed).best_move() == winning_move)
+            .count()
+    };
+    assert_eq!(20, winning_searches(Some((0.0, 100.0))));
+    assert!(winning_searches(None) < 15);
+}
+
+#[test]
+#[should_panic(expected = "reward_bounds must have low < high")]
+pub fn empty_reward_bounds_panic() {
+    IsmctsBuilder::<Nim>::new().reward_bounds(1.0, 1.0);
+}
+
+#[test]
+#[should_panic(expected = "reward_bounds must have low < high")]
+pub fn empty_reward_bounds_panic_when_searching() {
+    let mut ismcts = IsmctsHandler::new(Nim::new(vec![1, 2]));
+    ismcts.config_mut().reward_bounds = Some((1.0, 1.0));
+    ismcts.run_iterations(1, 10);
+}
+
+#[test]
+#[cfg(all(feature = "threads", not(target_arch = "wasm32")))]
+pub fn pondering_searches_until_stopped() {
+    let ismcts = IsmctsHandler::new(Nim::new(vec![1, 2, 4]));
+    let pondering = ismcts.start_pondering(2);
+    while ismcts.lifetime_iterations() < 100 {
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    pondering.stop();
+
+    let iterations = ismcts.lifetime_iterations();
+    assert_eq!(iterations, ismcts.total_visits());
+    std::thread::sleep(Duration::from_millis(10));
+    assert_eq!(iterations, ismcts.lifetime_iterations());
+
+    // The handler keeps searching normally afterwards
+    ismcts.run_iterations(1, 100);
+    assert_eq!(iterations + 100, ismcts.total_visits());
+}
+
+#[test]
+pub fn ranked_moves_best_first() {
+    let ismcts = IsmctsHandler::with_seed(Nim::new(vec![1, 2, 4]), 0);
+    ismcts.run_iterations(1, 1000);
+
+    let ranked = ismcts.ranked_moves();
+    assert_eq!(
+        ismcts.root_node.children.read().unwrap().len(),
+        ranked.len()
+    );
+    assert_eq!(
+        ismcts.total_visits(),
+        ranked.iter().map(|m| m.visits).sum::<usize>()
+    );
+    assert_eq!(ismcts.best_move(), Some(ranked[0].mov));
+    for pair in ranked.windows(2) {
+        assert!(pair[0].visits >= pair[1].visits);
+    }
+    for m in &ranked {
+        assert!(m.availability >= m.visits);
+        assert!(m.ucb1 >= m.mean_reward.unwrap());
+    }
+}
+
+#[test]
+pub fn availability_report_ratios() {
+    let ismcts = IsmctsHandler::with_seed(Nim::new(vec![1, 2, 4]), 0);
+    ismcts.run_iterations(1, 1000);
+
+    let report = ismcts.availability_report();
+    assert_eq!(7, report.len());
+    for (_, visits, availability, ratio) in report {
+        // Counted from when the move was expanded
+        assert!(availability <= 1000);
+        assert_eq!(visits as f64 / availability as f64, ratio);
+        assert!(ratio > 0.0 && ratio <= 1.0);
+    }
+}
+
+#[test]
+pub fn ensure_visits_reaches_threshold() {
+    let ismcts = IsmctsHandler::new(Nim::new(vec![1, 2, 4]));
+    ismcts.run_iterations(1, 10);
+    assert_eq!(None, ismcts.best_move_min_visits(500));
+
+    assert!(ismcts.ensure_visits(2, 500));
+    assert!(ismcts.max_visits() >= 500);
+    assert_eq!(ismcts.best_move(), ismcts.best_move_min_visits(500));
+
+    // Nothing to search in a finished game
+    let ismcts = IsmctsHandler::new(Nim::new(vec![0]));
+    assert!(!ismcts.ensure_visits(1, 10));
+    assert_eq!(None, ismcts.best_move_min_visits(0));
+
+    // The root's children are never updated, so the search gives up
+    let mut ismcts = IsmctsHandler::new(Nim::new(vec![1, 2, 4]));
+    ismcts.config_mut().backup_depth = Some(0);
+    ismcts.run_iterations(1, 10);
+    assert!(!ismcts.ensure_visits(1, 10));
+}
+
+#[test]
+pub fn partitioned_search_independent_of_thread_count() {
+    let search = |n_threads| {
+        let ismcts = IsmctsHandler::with_seed(Nim::new(vec![2, 3, 4]), 0);
+        ismcts.run_iterations_partitioned(n_threads, 1001, 8);
+        ismcts
+    };
+    let single = search(1);
+    assert_eq!(1001, single.total_visits());
+    for n_threads in [2, 4] {
+        let parallel = search(n_threads);
+        assert_eq!(single.best_move(), parallel.best_move());
+        assert_eq!(single.move_statistics(), parallel.move_statistics());
+        assert_eq!(single.node_count(), parallel.node_count());
+    }
+}
+
+#[test]
+pub fn draw_rate_of_drawn_game() {
+    let ismcts = IsmctsHandler::with_seed(PickHigherGame::default(), 0);
+    assert_eq!(0.0, ismcts.draw_rate());
+    ismcts.run_iterations(1, 2000);
+
+    // The second player draws against 2 and wins against anything else, so picking 2 is best
+    // and mostly draws
+    assert_eq!(Some(2), ismcts.best_move());
+    let children = ismcts.root_node.children.read().unwrap();
+    for child in children.iter() {
+        let draw_rate = child.statistics.snapshot().draw_rate();
+        if child.mov == Some(2) {
+            assert!(draw_rate > 0.9, "{}", draw_rate);
+        } else {
+            assert!(draw_rate < 0.2, "{}", draw_rate);
+        }
+    }
+    assert!(ismcts.draw_rate() > 0.5 && ismcts.draw_rate() < 1.0);
+}
+
+#[test]
+pub fn terminal_leaves_are_not_simulated() {
+    // The only move ends the game
+    let mut ismcts = IsmctsHandler::new(Instrumented::new(Nim::new(vec![1])));
+    ismcts.config_mut().warmup_rollouts = 10;
+    ismcts.config_mut().leaf_parallel = 4;
+    ismcts.run_iterations(1, 100);
+
+    assert_eq!(0, ismcts.state().counts.rollouts.load(Ordering::SeqCst));
+    let children = ismcts.root_children_sorted_by(|c| c.visit_count);
+    assert_eq!(1, children.len());
+    assert!(children[0].terminal);
+    assert_eq!(100, children[0].visit_count);
+    assert_eq!(Some(1.0), children[0].mean_reward());
+}
+
+#[test]
+pub fn player_rewards_of_general_sum_game() {
+    let mut ismcts = IsmctsHandler::new(PayoffPickGame::default());
+    ismcts.run_iterations(1, 10);
+    assert!(ismcts
+        .root_children_sorted_by(|c| c.visit_count)
+        .iter()
+        .all(|c| c.player_rewards.is_empty()));
+
+    ismcts.reset(PayoffPickGame::default());
+    ismcts.config_mut().players = Some(vec![0, 1, 2]);
+    ismcts.run_iterations(1, 200);
+
+    // The first player picks what's best for them, though the third player would prefer the other
+    assert_eq!(Some(0), ismcts.best_move());
+    for child in ismcts.root_children_sorted_by(|c| c.visit_count) {
+        let visits = child.visit_count as f64;
+        let payoffs = PayoffPickGame::PAYOFFS[child.mov.unwrap()];
+        let expected: Vec<_> = payoffs.iter().map(|p| p * visits).collect();
+        assert_eq!(expected, child.player_rewards);
+        assert_eq!(child.reward, child.player_rewards[0]);
+    }
+}
+
+#[test]
+pub fn backup_depth_stops_at_ancestors() {
+    let visits_by_depth = |ismcts: &IsmctsHandler<TenMoveGame>| {
+        let mut visits = vec![0; 11];
+        for node in ismcts.nodes() {
+            visits[node.depth] += node.visit_count;
+        }
+        visits
+    };
+    let mut ismcts = IsmctsHandler::with_seed(TenMoveGame::default(), 0);
+    ismcts.run_iterations(1, 100);
+    ismcts.config_mut().backup_depth = Some(1);
+
+    let before = visits_by_depth(&ismcts);
+    ismcts.run_iterations(1, 1);
+    let after = visits_by_depth(&ismcts);
+
+    // Only the expanded node and its parent were updated
+    let updated: Vec<_> = (0..11).filter(|&d| after[d] != before[d]).collect();
+    assert_eq!(2, updated.len());
+    assert_eq!(updated[0] + 1, updated[1]);
+    assert!(updated[1] > 1, "expanded below the root's children");
+    for d in updated {
+        assert_eq!(before[d] + 1, after[d]);
+    }
+}
+
+#[test]
+pub fn children_summary_matches_tree() {
+    let ismcts = IsmctsHandler::new(TenMoveGame::default());
+    ismcts.run_iterations(2, ITERATIONS);
+
+    let summary = ismcts.children_summary();
+    let children = ismcts.root_node.children.read().unwrap();
+    assert_eq!(children.len(), summary.len());
+    for (child, summary) in children.iter().zip(&summary) {
+        let statistics = child.statistics.snapshot();
+        assert_eq!(child.mov, Some(summary.mov));
+        assert_eq!(statistics.visit_count, summary.visits);
+        assert_eq!(statistics.availability_count, summary.availability);
+        assert_eq!(Some(statistics.mean()), summary.mean_reward);
+    }
+    assert_eq!(
+        ismcts.total_visits(),
+        summary.iter().map(|c| c.visits).sum::<usize>()
+    );
+}
+
+#[test]
+pub fn children_summary_ucb1_by_hand() {
+    let mut ismcts = IsmctsHandler::with_exploration(TenMoveGame::default(), 1.5);
+    let child = add_visited_child(&ismcts, 1, 10, 6.0);
+    child
+        .statistics
+        .availability_count
+        .store(40, Ordering::Relaxed);
+    add_visited_child(&ismcts, 2, 0, 0.0);
+    ismcts
+        .root_node
+        .statistics
+        .visit_count
+        .store(100, Ordering::Relaxed);
+
+    let summary = ismcts.children_summary();
+    let expected = 0.6 + 1.5 * (40f64.ln() / 10.0).sqrt();
+    assert!(
+        (summary[0].ucb1 - expected).abs() < 1e-12,
+        "{:?}",
+        summary[0]
+    );
+    assert_eq!(f64::INFINITY, summary[1].ucb1);
+
+    ismcts.config_mut().ucb_availability = UcbAvailability::Parent;
+    let expected = 0.6 + 1.5 * (100f64.ln() / 10.0).sqrt();
+    assert!((ismcts.children_summary()[0].ucb1 - expected).abs() < 1e-12);
+
+    // The score follows the selection formula, here PUCT with the child's prior
+    child.prior.store(0.3);
+    ismcts.config_mut().selection = Selection::Puct;
+    let expected = 0.6 + 1.5 * 0.3 * 100f64.sqrt() / 11.0;
+    assert!((ismcts.children_summary()[0].ucb1 - expected).abs() < 1e-12);
+
+    // And is mixed with the all-moves-as-first mean under RAVE
+    child.statistics.amaf_visits.store(20, Ordering::Relaxed);
+    child.statistics.amaf_reward.store(2.0);
+    ismcts.config_mut().rave = Some(30.0);
+    let beta = (30.0f64 / (3.0 * 10.0 + 30.0)).sqrt();
+    let expected = expected + beta * (0.1 - 0.6);
+    assert!((ismcts.children_summary()[0].ucb1 - expected).abs() < 1e-12);
+}
+
+#[test]
+pub fn exploitation_value_skips_unvisited_moves() {
+    let ismcts = IsmctsHandler::new(TenMoveGame::default());
+    add_visited_child(&ismcts, 1, 0, 0.0);
+    add_visited_child(&ismcts, 2, 4, 0.0);
+    add_visited_child(&ismcts, 3, 4, 1.0);
+
+    let values = ismcts.exploitation_value();
+    assert_eq!(vec![(1, f64::NEG_INFINITY), (2, 0.0), (3, 0.25)], values);
+    let best = values
+        .into_iter()
+        .max_by_key(|(_, value)| OrderedFloat::from(*value))
+        .map(|(mov, _)| mov);
+    assert_eq!(Some(3), best);
+
+    // Even a losing visited move beats an unvisited one
+    let ismcts = IsmctsHandler::new(TenMoveGame::default());
+    add_visited_child(&ismcts, 1, 0, 0.0);
+    add_visited_child(&ismcts, 2, 4, 0.0);
+    let best = ismcts
+        .exploitation_value()
+        .into_iter()
+        .max_by_key(|(_, value)| OrderedFloat::from(*value))
+        .map(|(mov, _)| mov);
+    assert_eq!(Some(2), best);
+}
+
+#[test]
+pub fn ties_go_to_earliest_legal_move() {
+    let ismcts = IsmctsHandler::new(TenMoveGame::default());
+    for mov in [3, 1, 2] {
+        add_visited_child(&ismcts, mov, 10, 5.0);
+    }
+    let run = ismcts.search_run(1);
+    let select = |legal_moves: &[u8]| {
+        ismcts
+            .root_node
+            .select_child(legal_moves, &run, None, None)
+            .unwrap()
+            .mov
+    };
+    for _ in 0..10 {
+        assert_eq!(Some(2), select(&[2, 1, 3]));
+        assert_eq!(Some(1), select(&[1, 2, 3]));
+        assert_eq!(Some(3), select(&[3, 2, 1]));
+    }
+    assert_eq!(Some(1), ismcts.best_move());
+}
+
+#[test]
+pub fn priority_move_expanded_first() {
+    let priority_move = NimMove { heap: 2, amount: 3 };
+    let nim = Instrumented {
+        priority_move: Some(priority_move),
+        ..Instrumented::new(Nim::new(vec![1, 2, 4]))
+    };
+    for seed in 0..20 {
+        let ismcts = IsmctsHandler::with_seed(nim.clone(), seed);
+        ismcts.run_iterations(1, 1);
+        let children = ismcts.children_summary();
+        assert_eq!(1, children.len());
+        assert_eq!(priority_move, children[0].mov);
+    }
+
+    // The rest are then expanded in random order
+    let ismcts = IsmctsHandler::new(nim);
+    ismcts.run_iterations(1, 7);
+    assert_eq!(7, ismcts.children_summary().len());
+}
+
+#[test]
+pub fn pruned_move_never_expanded() {
+    let pruned_move = NimMove { heap: 2, amount: 1 };
+    let nim = Instrumented {
+        pruned_move: Some(pruned_move),
+        ..Instrumented::new(Nim::new(vec![1, 2, 4]))
+    };
+    let mut ismcts = IsmctsHandler::new(nim);
+    ismcts.run_iterations(2, 2000);
+
+    assert!(ismcts.nodes().count() > 50);
+    assert!(ismcts.nodes().all(|n| n.mov != Some(pruned_move)));
+    // Though it's never searched, it can still be played
+    assert_eq!(Err(MoveError::Unexplored), ismcts.make_move(&pruned_move));
+    assert_eq!(vec![1, 2, 3], ismcts.state().game.heaps);
+}
+
+#[test]
+#[cfg(not(all(feature = "threads", not(target_arch = "wasm32"))))]
+pub fn search_without_threads_runs_on_calling_thread() {
+    let mut ismcts = IsmctsHandler::new(Instrumented::<TenMoveGame>::default());
+    ismcts.config_mut().leaf_parallel = 2;
+    ismcts.run_iterations(4, 50);
+    ismcts.run_timed(4, Duration::from_millis(20));
+    ismcts.run_until_confident(2, Duration::from_millis(20), usize::MAX);
+
+    assert!(ismcts.total_visits() > 200);
+    let threads = ismcts.state().counts.threads.lock().unwrap();
+    assert!(threads.iter().all(|&t| t == std::thread::current().id()));
+}
+
+#[test]
+pub fn policy_targets_of_self_play() {
+    let mut ismcts = IsmctsHandler::new(Nim::new(vec![1, 2, 3]));
+    assert_eq!(
+        vec![1.0 / 6.0; 6],
+        ismcts
+            .record_policy_target()
+            .visit_distribution
+            .iter()
+            .map(|(_, share)| *share)
+            .collect::<Vec<_>>()
+    );
+
+    let mut targets = Vec::new();
+    loop {
+        ismcts.run_iterations(1, 200);
+        let Some(mov) = ismcts.best_move() else {
+            break;
+        };
+        let target = ismcts.record_policy_target();
+        let total: f64 = target.visit_distribution.iter().map(|(_, s)| s).sum();
+        assert!((total - 1.0).abs() < 1e-9);
+        let total_visits = ismcts.total_visits() as f64;
+        for child in ismcts.children_summary() {
+            let share = target
+                .visit_distribution
+                .iter()
+                .find(|(m, _)| *m == child.mov)
+                .unwrap()
+                .1;
+            assert_eq!(child.visits as f64 / total_visits, share);
+        }
+        targets.push(target);
+        ismcts.make_move(&mov).unwrap();
+    }
+
+    for target in &mut targets {
+        target.attach_result(ismcts.state(), ismcts.config());
+    }
+    // Whoever made the last move won, and the players alternate
+    for (i, target) in targets.iter().rev().enumerate() {
+        let expected = if i % 2 == 0 { 1.0 } else { 0.0 };
+        assert_eq!(Some(expected), target.result);
     }
 }